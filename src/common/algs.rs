@@ -1,8 +1,15 @@
 use crate::expression::{
     error::{Error, SolveErr},
-    token::{BinaryOp, Token, UnaryOpRight},
+    token::{BinaryOp, Token, UnaryOpLeft, UnaryOpRight},
 };
-use fraction::{BigFraction, BigUint, GenericFraction, Ratio, Sign, Zero};
+use fraction::{
+    BigFraction, BigInt, BigUint, GenericFraction, Integer, Ratio, Sign, Signed, ToPrimitive, Zero,
+};
+
+/// longest repetend searched by "period", longer ones are too slow to find
+const MAX_PERIOD: usize = 100_000;
+/// biggest operand of "fact" and "dereng", bigger ones have too many digits to be shown
+const MAX_FACT: u64 = 1000;
 
 pub fn disp(first: u64, last: u64) -> BigUint {
     if first == last {
@@ -60,31 +67,44 @@ pub fn to_u64(num: &BigFraction) -> Option<u64> {
 }
 
 pub fn exp(base: &BigFraction, exp: &BigFraction) -> Result<BigFraction, Error> {
-    let err = || {
-        SolveErr::OperIllegalValues(vec![
+    let tokens = || {
+        vec![
             Token::Number(base.clone()),
             Token::from(BinaryOp::Exp),
             Token::Number(exp.clone()),
-        ])
+        ]
     };
+    let err = || SolveErr::OperIllegalValues(tokens());
     if base.is_nan() || base.is_infinite() || exp.is_nan() || exp.is_infinite() {
         None.ok_or_else(err)?;
     }
-    let exp_ = to_i32(exp).ok_or_else(err)?;
+    let exp_numer = numer(exp).and_then(|num| to_i32(&num)).ok_or_else(err)?;
+    let exp_denom = denom(exp).and_then(|num| to_u64(&num)).ok_or_else(err)?;
+    let exp_denom = u32::try_from(exp_denom).ok().ok_or_else(err)?;
+    let negative = base.is_sign_negative() && !base.is_zero();
+    if (base.is_zero() && exp_numer < 0) || (negative && exp_denom % 2 == 0) {
+        None.ok_or_else(err)?;
+    }
     let base_ = to_ratio(base).ok_or_else(err)?;
-    let res = base_.pow(exp_);
-    let sign = match (
-        base.is_sign_positive(),
-        (exp % BigUint::from(2u32)).is_zero(),
-    ) {
-        (true, true) => Sign::Plus,
-        (true, false) => Sign::Plus,
-        (false, true) => Sign::Plus,
-        (false, false) => Sign::Minus,
+    let root = nth_root(&base_, exp_denom).ok_or_else(|| SolveErr::IrrationalResult(tokens()))?;
+    let res = root.pow(exp_numer);
+    let sign = match negative && exp_numer % 2 != 0 {
+        true => Sign::Minus,
+        false => Sign::Plus,
     };
     Ok(BigFraction::Rational(sign, res))
 }
 
+/// n-th root of num, if it is a rational number
+pub fn nth_root(num: &Ratio<BigUint>, n: u32) -> Option<Ratio<BigUint>> {
+    let root = |int: &BigUint| Some(int.nth_root(n)).filter(|root| &root.pow(n) == int);
+    Some(Ratio::new(root(num.numer())?, root(num.denom())?))
+}
+
+pub fn root(num: &BigFraction, n: u32) -> Result<BigFraction, Error> {
+    exp(num, &BigFraction::new(1u8, n))
+}
+
 pub fn fact(num: &BigFraction) -> Result<BigFraction, Error> {
     if num.is_zero() {
         return Ok(BigFraction::from(1));
@@ -99,7 +119,7 @@ pub fn fact(num: &BigFraction) -> Result<BigFraction, Error> {
         GenericFraction::Rational(_, _) => (),
         _ => None.ok_or_else(err)?,
     }
-    let num = to_u64(num).ok_or_else(err)?;
+    let num = to_u64(num).filter(|num| *num <= MAX_FACT).ok_or_else(err)?;
     Ok(BigFraction::from(disp(1u64, num)))
 }
 
@@ -109,26 +129,275 @@ pub fn dereng(num: &BigFraction) -> Result<BigFraction, Error> {
     }
     let err = || {
         SolveErr::OperIllegalValues(vec![
+            Token::from(UnaryOpLeft::Derang),
             Token::Number(num.clone()),
-            Token::from(UnaryOpRight::Fact),
         ])
     };
     match num {
         GenericFraction::Rational(_, _) => (),
         _ => None.ok_or_else(err)?,
     }
-    let num = to_u64(num).ok_or_else(err)?;
-    let fact_num = fact(&BigFraction::from(num))?;
-    let mut res = Zero::zero();
-    for i in 2..num {
-        let sign = i as i64 % 2 * -2 + 1;
-        let tmp = fact(&BigFraction::from(i))?;
-        res += &fact_num / tmp * sign;
+    let num = to_u64(num).filter(|num| *num <= MAX_FACT).ok_or_else(err)?;
+    // !n = n * !(n - 1) + (-1)^n
+    let mut res = BigInt::from(1);
+    for i in 1..=num {
+        res = res * i + if i % 2 == 0 { 1 } else { -1 };
+    }
+    Ok(BigFraction::from(res))
+}
+
+/// digits after the decimal point needed to write num, if they are finite
+pub fn decimal_places(num: &BigFraction) -> Option<usize> {
+    let mut denom = to_ratio(num)?.denom().clone();
+    let (mut twos, mut fives) = (0, 0);
+    while denom.is_even() {
+        denom /= 2u8;
+        twos += 1;
+    }
+    while (&denom % 5u8).is_zero() {
+        denom /= 5u8;
+        fives += 1;
+    }
+    (denom == BigUint::from(1u8)).then_some(twos.max(fives))
+}
+
+pub fn gcd(a: &BigFraction, b: &BigFraction) -> Option<BigFraction> {
+    let (a, b) = (to_ratio(a)?, to_ratio(b)?);
+    let numer = a.numer().gcd(b.numer());
+    let denom = a.denom().lcm(b.denom());
+    Some(BigFraction::new(numer, denom))
+}
+
+pub fn lcm(a: &BigFraction, b: &BigFraction) -> Option<BigFraction> {
+    let (a, b) = (to_ratio(a)?, to_ratio(b)?);
+    let numer = a.numer().lcm(b.numer());
+    let denom = a.denom().gcd(b.denom());
+    Some(BigFraction::new(numer, denom))
+}
+
+pub fn sign(num: &BigFraction) -> BigFraction {
+    if num.is_zero() {
+        return BigFraction::zero();
+    }
+    num.signum()
+}
+
+pub fn numer(num: &BigFraction) -> Option<BigFraction> {
+    let numer = to_ratio(num)?.numer().clone();
+    match num.is_sign_negative() {
+        true => Some(BigFraction::new_neg(numer, 1u8)),
+        false => Some(BigFraction::new(numer, 1u8)),
+    }
+}
+
+pub fn denom(num: &BigFraction) -> Option<BigFraction> {
+    Some(BigFraction::new(to_ratio(num)?.denom().clone(), 1u8))
+}
+
+/// digits before the repetend of 1/denom in the given base, and digits of the repetend
+/// (0 if the digits are finite, None if longer than MAX_PERIOD)
+pub fn period(denom: &BigUint, base: u32) -> (usize, Option<usize>) {
+    let mut denom = denom.clone();
+    let mut fixed = 0;
+    loop {
+        let common = denom.gcd(&BigUint::from(base));
+        if common == BigUint::from(1u8) {
+            break;
+        }
+        denom /= common;
+        fixed += 1;
+    }
+    if denom == BigUint::from(1u8) {
+        return (fixed, Some(0));
+    }
+    // the period is the smallest n with base^n = 1 (mod denom)
+    let mut rem = BigUint::from(base) % &denom;
+    for len in 1..=MAX_PERIOD {
+        if rem == BigUint::from(1u8) {
+            return (fixed, Some(len));
+        }
+        rem = rem * base % &denom;
+    }
+    (fixed, None)
+}
+
+/// num written in the given base (from 2 to 36), with the repeating part of its
+/// fractional digits between brackets, "1/3" in base 2 => "0.(01)", or overlined.
+/// At most max_len fractional digits are written, followed by "..." and the period length
+pub fn as_base(num: &BigFraction, base: u32, max_len: usize, overline: bool) -> Option<String> {
+    if !(2..=36).contains(&base) {
+        return None;
+    }
+    let ratio = to_ratio(num)?;
+    let denom = ratio.denom();
+    let sign = if num.is_sign_negative() && !num.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+    let int = (ratio.numer() / denom).to_str_radix(base).to_uppercase();
+    let (fixed, period) = period(denom, base);
+    let len = fixed + period.unwrap_or(usize::MAX - fixed);
+    let mut rem = ratio.numer() % denom;
+    let mut digits = String::new();
+    for _ in 0..len.min(max_len) {
+        rem *= base;
+        let digit = (&rem / denom).to_u32()?;
+        rem %= denom;
+        digits.push(char::from_digit(digit, base)?.to_ascii_uppercase());
+    }
+    let frac = match (len <= max_len, period) {
+        (true, Some(0)) => digits,
+        (true, _) if overline => {
+            let (fixed, repetend) = digits.split_at(fixed);
+            let repetend = repetend.chars().flat_map(|c| [c, '\u{305}']);
+            fixed.chars().chain(repetend).collect()
+        }
+        (true, _) => format!("{}({})", &digits[..fixed], &digits[fixed..]),
+        (false, Some(0)) => format!("{digits}..."),
+        (false, Some(period)) => format!("{digits}... (period length {period})"),
+        (false, None) => format!("{digits}... (period length over {MAX_PERIOD})"),
+    };
+    match frac.is_empty() {
+        true => Some(format!("{sign}{int}")),
+        false => Some(format!("{sign}{int}.{frac}")),
+    }
+}
+
+/// num written as "m.mmme[exp]", rounded half away from zero to sig_digits significant digits.
+/// With eng the exponent is a multiple of 3, so the mantissa is between 1 and 1000
+pub fn as_scientific(num: &BigFraction, sig_digits: usize, eng: bool) -> Option<String> {
+    let ratio = to_ratio(num)?;
+    if sig_digits == 0 {
+        return None;
+    }
+    if ratio.is_zero() {
+        return Some(String::from("0"));
+    }
+    let sign = if num.is_sign_negative() { "-" } else { "" };
+    let pow10 = |exp: i64| {
+        let pow = BigUint::from(10u8).pow(exp.unsigned_abs() as u32);
+        match exp < 0 {
+            true => Ratio::new(BigUint::from(1u8), pow),
+            false => Ratio::from_integer(pow),
+        }
+    };
+    // biggest exp with 10^exp <= num
+    let len = |int: &BigUint| int.to_string().len() as i64;
+    let mut exp = len(ratio.numer()) - len(ratio.denom());
+    if ratio < pow10(exp) {
+        exp -= 1;
+    }
+    let scaled = ratio / pow10(exp + 1 - sig_digits as i64);
+    let half = Ratio::new(BigUint::from(1u8), BigUint::from(2u8));
+    let mut mantissa = (scaled + half).to_integer();
+    // rounding up can add a digit, like "9.99" => "10.0"
+    if len(&mantissa) > sig_digits as i64 {
+        mantissa /= 10u8;
+        exp += 1;
+    }
+    let shift = if eng { exp.rem_euclid(3) as usize } else { 0 };
+    let mut digits = mantissa.to_string();
+    while digits.len() <= shift {
+        digits.push('0');
+    }
+    let (int, frac) = digits.split_at(shift + 1);
+    let frac = frac.trim_end_matches('0');
+    let exp = exp - shift as i64;
+    match frac.is_empty() {
+        true => Some(format!("{sign}{int}e{exp}")),
+        false => Some(format!("{sign}{int}.{frac}e{exp}")),
+    }
+}
+
+/// num as an integer followed by a proper fraction: "-7/3" => "-2 1/3"
+pub fn as_mixed(num: &BigFraction) -> Option<String> {
+    let ratio = to_ratio(num)?;
+    let (int, rem) = ratio.numer().div_rem(ratio.denom());
+    let denom = ratio.denom();
+    let sign = if num.is_sign_negative() && !num.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+    match (int.is_zero(), rem.is_zero()) {
+        (_, true) => Some(format!("{sign}{int}")),
+        (true, false) => Some(format!("{sign}{rem}/{denom}")),
+        (false, false) => Some(format!("{sign}{int} {rem}/{denom}")),
+    }
+}
+
+/// terms of the continued fraction of num, all positive except the first:
+/// "7/3" => [2, 3], "-7/3" => [-3, 1, 2]
+pub fn continued_fraction(num: &BigFraction) -> Option<Vec<BigInt>> {
+    let ratio = to_ratio(num)?;
+    let mut numer = BigInt::from(ratio.numer().clone());
+    let mut denom = BigInt::from(ratio.denom().clone());
+    if num.is_sign_negative() {
+        numer = -numer;
+    }
+    let mut terms = Vec::new();
+    while !denom.is_zero() {
+        let term = numer.div_floor(&denom);
+        let rem = numer - &term * &denom;
+        terms.push(term);
+        (numer, denom) = (denom, rem);
     }
-    if num >= 2 {
-        res += num as i64 % 2 * -2 + 1;
+    Some(terms)
+}
+
+/// continued fraction of num written as "[2; 3]"
+pub fn as_continued(num: &BigFraction) -> Option<String> {
+    let terms = continued_fraction(num)?;
+    let rest = terms[1..].iter().map(|term| term.to_string());
+    match terms.len() {
+        1 => Some(format!("[{}]", terms[0])),
+        _ => Some(format!(
+            "[{}; {}]",
+            terms[0],
+            rest.collect::<Vec<_>>().join(", ")
+        )),
     }
-    Ok(res)
+}
+
+/// closest fraction to num whose denominator is at most max_denom
+pub fn best_approx(num: &BigFraction, max_denom: &BigUint) -> Option<BigFraction> {
+    if max_denom.is_zero() {
+        return None;
+    }
+    let max_denom = BigInt::from(max_denom.clone());
+    let fraction = |numer: &BigInt, denom: &BigInt| {
+        let res = BigFraction::new(numer.magnitude().clone(), denom.magnitude().clone());
+        match numer.is_negative() {
+            true => -res,
+            false => res,
+        }
+    };
+    // the last two convergents h/k, starting from 0/1 and 1/0
+    let (mut h0, mut h1) = (BigInt::zero(), BigInt::from(1));
+    let (mut k0, mut k1) = (BigInt::from(1), BigInt::zero());
+    for term in continued_fraction(num)? {
+        let k2 = &term * &k1 + &k0;
+        if k2 > max_denom {
+            // the best approximation is either the last convergent or the closest
+            // semiconvergent (t * h1 + h0) / (t * k1 + k0) within max_denom
+            let convergent = fraction(&h1, &k1);
+            let t = (&max_denom - &k0) / &k1;
+            if t.is_zero() {
+                return Some(convergent);
+            }
+            let semi = fraction(&(&t * &h1 + &h0), &(&t * &k1 + &k0));
+            let dist = |other: &BigFraction| (other.clone() - num.clone()).abs();
+            return match dist(&semi) < dist(&convergent) {
+                true => Some(semi),
+                false => Some(convergent),
+            };
+        }
+        let h2 = &term * &h1 + &h0;
+        (h0, h1) = (h1, h2);
+        (k0, k1) = (k1, k2);
+    }
+    Some(fraction(&h1, &k1))
 }
 
 #[cfg(test)]
@@ -154,11 +423,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_rational_exp() -> Result<(), Error> {
+        let actual1 = exp(&BigFraction::from(4), &BigFraction::new(1u8, 2u8))?;
+        let actual2 = exp(&BigFraction::new(8u8, 27u8), &BigFraction::new(2u8, 3u8))?;
+        let actual3 = exp(&BigFraction::from(-8), &BigFraction::new_neg(1u8, 3u8))?;
+        let actual4 = exp(&BigFraction::from(2), &BigFraction::new(1u8, 2u8));
+        let actual5 = exp(&BigFraction::from(-4), &BigFraction::new(1u8, 2u8));
+        let actual6 = exp(&BigFraction::from(0), &BigFraction::from(-1));
+        assert_eq!(actual1, BigFraction::from(2));
+        assert_eq!(actual2, BigFraction::new(4u8, 9u8));
+        assert_eq!(actual3, BigFraction::new_neg(1u8, 2u8));
+        assert!(matches!(
+            actual4,
+            Err(Error::Solve(SolveErr::IrrationalResult(_)))
+        ));
+        assert!(matches!(
+            actual5,
+            Err(Error::Solve(SolveErr::OperIllegalValues(_)))
+        ));
+        assert!(matches!(
+            actual6,
+            Err(Error::Solve(SolveErr::OperIllegalValues(_)))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_fact() -> Result<(), Error> {
         let actual1 = fact(&BigFraction::from(10))?;
         let expected1 = BigFraction::from(3628800);
         assert_eq!(actual1, expected1);
+        assert!(fact(&BigFraction::from(MAX_FACT)).is_ok());
+        assert!(matches!(
+            fact(&BigFraction::from(2_222_222)),
+            Err(Error::Solve(SolveErr::OperIllegalValues(_)))
+        ));
         Ok(())
     }
 
@@ -167,6 +467,105 @@ mod tests {
         let actual1 = dereng(&BigFraction::from(10))?;
         let expected1 = BigFraction::from(1334961);
         assert_eq!(actual1, expected1);
+        let small = [0, 1, 2, 3, 4].map(|n| dereng(&BigFraction::from(n)));
+        assert_eq!(small, [1, 0, 1, 2, 9].map(|n| Ok(BigFraction::from(n))));
+        assert!(matches!(
+            dereng(&BigFraction::from(2222)),
+            Err(Error::Solve(SolveErr::OperIllegalValues(_)))
+        ));
         Ok(())
     }
+
+    #[test]
+    fn test_gcd_lcm() {
+        let a = BigFraction::new(4u8, 9u8);
+        let b = BigFraction::new(2u8, 3u8);
+        assert_eq!(gcd(&a, &b), Some(BigFraction::new(2u8, 9u8)));
+        assert_eq!(lcm(&a, &b), Some(BigFraction::new(4u8, 3u8)));
+    }
+
+    #[test]
+    fn test_as_base() {
+        let num = |numer: i64, denom: u64| BigFraction::from(numer) / BigFraction::from(denom);
+        let as_base =
+            |num: &BigFraction, base: u32, max_len: usize| as_base(num, base, max_len, false);
+        assert_eq!(as_base(&num(255, 1), 16, 10), Some(String::from("FF")));
+        assert_eq!(as_base(&num(-11, 1), 2, 10), Some(String::from("-1011")));
+        assert_eq!(as_base(&num(125, 4), 16, 10), Some(String::from("1F.4")));
+        assert_eq!(as_base(&num(1, 3), 2, 10), Some(String::from("0.(01)")));
+        assert_eq!(as_base(&num(1, 6), 10, 10), Some(String::from("0.1(6)")));
+        assert_eq!(
+            as_base(&num(1, 7), 10, 3),
+            Some(String::from("0.142... (period length 6)"))
+        );
+        assert_eq!(
+            as_base(&num(1, 1024), 10, 3),
+            Some(String::from("0.000..."))
+        );
+        assert_eq!(as_base(&num(35, 36), 36, 10), Some(String::from("0.Z")));
+        assert_eq!(as_base(&num(0, 1), 8, 10), Some(String::from("0")));
+        assert_eq!(as_base(&num(1, 1), 37, 10), None);
+    }
+
+    #[test]
+    fn test_as_scientific() {
+        let num = |str: &str| BigFraction::from_str(str).unwrap();
+        let sci = |str: &str, sig_digits: usize| as_scientific(&num(str), sig_digits, false);
+        let eng = |str: &str, sig_digits: usize| as_scientific(&num(str), sig_digits, true);
+        assert_eq!(
+            sci("602200000000000000000000", 4),
+            Some(String::from("6.022e23"))
+        );
+        assert_eq!(
+            sci("-0.00000000000000000016", 3),
+            Some(String::from("-1.6e-19"))
+        );
+        assert_eq!(sci("9.996", 3), Some(String::from("1e1")));
+        assert_eq!(sci("1234.5", 10), Some(String::from("1.2345e3")));
+        assert_eq!(sci("0", 3), Some(String::from("0")));
+        assert_eq!(eng("12345", 3), Some(String::from("12.3e3")));
+        assert_eq!(eng("0.000123", 3), Some(String::from("123e-6")));
+        assert_eq!(eng("100000", 1), Some(String::from("100e3")));
+        assert_eq!(sci("1", 0), None);
+    }
+
+    #[test]
+    fn test_period() {
+        let period = |denom: u32| period(&BigUint::from(denom), 10);
+        assert_eq!(period(8), (3, Some(0)));
+        assert_eq!(period(12), (2, Some(1)));
+        assert_eq!(period(7), (0, Some(6)));
+        assert_eq!(period(97 * 40), (3, Some(96)));
+        let overline = as_base(&(BigFraction::from(1) / BigFraction::from(6)), 10, 20, true);
+        assert_eq!(overline, Some(String::from("0.16\u{305}")));
+    }
+
+    #[test]
+    fn test_mixed_continued() {
+        let num = |numer: i64, denom: u64| BigFraction::from(numer) / BigFraction::from(denom);
+        assert_eq!(as_mixed(&num(7, 3)), Some(String::from("2 1/3")));
+        assert_eq!(as_mixed(&num(-7, 3)), Some(String::from("-2 1/3")));
+        assert_eq!(as_mixed(&num(-1, 3)), Some(String::from("-1/3")));
+        assert_eq!(as_mixed(&num(6, 3)), Some(String::from("2")));
+        assert_eq!(as_continued(&num(7, 3)), Some(String::from("[2; 3]")));
+        assert_eq!(as_continued(&num(-7, 3)), Some(String::from("[-3; 1, 2]")));
+        assert_eq!(
+            as_continued(&num(415, 93)),
+            Some(String::from("[4; 2, 6, 7]"))
+        );
+        assert_eq!(as_continued(&num(5, 1)), Some(String::from("[5]")));
+    }
+
+    #[test]
+    fn test_best_approx() {
+        let pi = BigFraction::from_str("3.14159265358979323846").unwrap();
+        let best = |max_denom: u32| best_approx(&pi, &BigUint::from(max_denom));
+        assert_eq!(best(1), Some(BigFraction::from(3)));
+        assert_eq!(best(7), Some(BigFraction::new(22u8, 7u8)));
+        assert_eq!(best(100), Some(BigFraction::new(311u16, 99u16)));
+        assert_eq!(best(1000), Some(BigFraction::new(355u16, 113u16)));
+        assert_eq!(best(0), None);
+        let neg = best_approx(&-pi.clone(), &BigUint::from(7u8));
+        assert_eq!(neg, Some(-BigFraction::new(22u8, 7u8)));
+    }
 }
@@ -0,0 +1,350 @@
+use super::algs;
+use fraction::{BigFraction, BigInt, BigUint, Integer, Ratio, Signed, ToPrimitive, Zero};
+
+/// extra digits used in intermediate results, so that rounding errors don't reach the result
+pub const GUARD: u64 = 10;
+/// biggest argument accepted by "exp", bigger ones have too many digits to be useful
+const MAX_EXP: i64 = 10_000;
+/// biggest root computed by extracting the integer root, bigger ones use "exp" and "ln"
+const MAX_ROOT: u32 = 64;
+/// most bits of a power raised exactly before its root, bigger ones use "exp" and "ln"
+const MAX_POW_BITS: u64 = 1_000_000;
+
+fn scale(digits: u64) -> BigInt {
+    BigInt::from(10u8).pow(digits as u32)
+}
+
+/// num * 10^digits, truncated to an integer
+fn to_fixed(num: &BigFraction, digits: u64) -> Option<BigInt> {
+    let ratio = algs::to_ratio(num)?;
+    let numer = BigInt::from(ratio.numer().clone()) * scale(digits);
+    let int = numer / BigInt::from(ratio.denom().clone());
+    match num.is_sign_negative() {
+        true => Some(-int),
+        false => Some(int),
+    }
+}
+
+/// int / 10^digits
+fn from_fixed(int: &BigInt, digits: u64) -> BigFraction {
+    let denom = scale(digits).magnitude().clone();
+    match int.is_negative() {
+        true => BigFraction::new_neg(int.magnitude().clone(), denom),
+        false => BigFraction::new(int.magnitude().clone(), denom),
+    }
+}
+
+/// num truncated to the given decimal digits
+pub fn truncate(num: &BigFraction, digits: u64) -> BigFraction {
+    match to_fixed(num, digits) {
+        Some(int) => from_fixed(&int, digits),
+        None => num.clone(),
+    }
+}
+
+/// num rounded to the given decimal digits, half away from zero
+pub fn round(num: &BigFraction, digits: u64) -> BigFraction {
+    let half = BigFraction::new(1u8, 2u8) / from_fixed(&scale(digits), 0);
+    match num.is_sign_negative() {
+        true => truncate(&(num.clone() - half), digits),
+        false => truncate(&(num.clone() + half), digits),
+    }
+}
+
+/// e^x, with x and the result scaled by s
+fn exp_fixed(x: &BigInt, s: &BigInt) -> BigInt {
+    if x.is_negative() {
+        return s * s / exp_fixed(&-x, s);
+    }
+    // e^x = (e^(x/2^k))^(2^k), with x/2^k below 1 to speed up the series
+    let mut halvings = 0;
+    let mut x = x.clone();
+    while &x > s {
+        x /= 2;
+        halvings += 1;
+    }
+    let mut sum = s.clone();
+    let mut term = s.clone();
+    for i in 1u32.. {
+        term = term * &x / (s * i);
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+    }
+    for _ in 0..halvings {
+        sum = &sum * &sum / s;
+    }
+    sum
+}
+
+/// atanh(z) = z + z^3/3 + z^5/5 + ..., with z and the result scaled by s
+fn atanh_fixed(z: &BigInt, s: &BigInt) -> BigInt {
+    let z2 = z * z / s;
+    let mut power = z.clone();
+    let mut sum = z.clone();
+    for i in 1u32.. {
+        power = power * &z2 / s;
+        let term = &power / (2 * i + 1);
+        if term.is_zero() {
+            break;
+        }
+        sum += term;
+    }
+    sum
+}
+
+/// atan(1/n) = 1/n - 1/(3n^3) + 1/(5n^5) - ..., with the result scaled by s
+fn atan_inv_fixed(n: u32, s: &BigInt) -> BigInt {
+    let n2 = BigInt::from(n) * n;
+    let mut power = s / n;
+    let mut sum = power.clone();
+    for i in 1u32.. {
+        power /= &n2;
+        let term = &power / (2 * i + 1);
+        if term.is_zero() {
+            break;
+        }
+        match i % 2 {
+            0 => sum += term,
+            _ => sum -= term,
+        }
+    }
+    sum
+}
+
+/// pi = 16 atan(1/5) - 4 atan(1/239), scaled by s
+fn pi_fixed(s: &BigInt) -> BigInt {
+    atan_inv_fixed(5, s) * 16 - atan_inv_fixed(239, s) * 4
+}
+
+/// sin(x) or cos(x), with x and the result scaled by s
+fn sin_cos_fixed(x: &BigInt, s: &BigInt, cos: bool) -> BigInt {
+    let x2 = x * x / s;
+    let mut term = if cos { s.clone() } else { x.clone() };
+    let mut sum = term.clone();
+    let first = if cos { 1u32 } else { 2u32 };
+    for i in (first..).step_by(2) {
+        term = -(term * &x2 / s) / (i * (i + 1));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+    }
+    sum
+}
+
+/// number of decimal digits in the integer part of num
+fn int_digits(num: &BigFraction) -> u64 {
+    let int = to_fixed(num, 0).unwrap_or_default();
+    int.magnitude().to_string().len() as u64
+}
+
+pub fn pi(digits: u64) -> BigFraction {
+    let work = digits + GUARD;
+    from_fixed(&(pi_fixed(&scale(work)) / scale(GUARD)), digits)
+}
+
+pub fn e(digits: u64) -> BigFraction {
+    let work = digits + GUARD;
+    from_fixed(
+        &(exp_fixed(&scale(work), &scale(work)) / scale(GUARD)),
+        digits,
+    )
+}
+
+/// golden ratio, (1 + sqrt(5)) / 2
+pub fn phi(digits: u64) -> BigFraction {
+    let sqrt_5 = root(&BigFraction::from(5), 2, digits + GUARD).unwrap_or_default();
+    truncate(
+        &((sqrt_5 + BigFraction::from(1)) / BigFraction::from(2)),
+        digits,
+    )
+}
+
+/// full turn in radians, 2 * pi
+pub fn tau(digits: u64) -> BigFraction {
+    truncate(&(pi(digits + GUARD) * BigFraction::from(2)), digits)
+}
+
+pub fn exp(x: &BigFraction, digits: u64) -> Option<BigFraction> {
+    if to_fixed(x, 0)?.abs() > BigInt::from(MAX_EXP) {
+        return None;
+    }
+    // every halving of x doubles the error of the final squarings, which is relative to
+    // the result, with about x / ln(10) < x / 2 integer digits
+    let int = to_fixed(x, 0)?.to_u64().unwrap_or_default();
+    let work = digits + GUARD + int_digits(x) * 2 + int / 2;
+    let s = scale(work);
+    let res = exp_fixed(&to_fixed(x, work)?, &s);
+    Some(from_fixed(&(res / scale(work - digits)), digits))
+}
+
+pub fn ln(x: &BigFraction, digits: u64) -> Option<BigFraction> {
+    let ratio = algs::to_ratio(x)?;
+    if x.is_sign_negative() || x.is_zero() {
+        return None;
+    }
+    // x = m * 2^k, with m in [1, 2)
+    let mut k = ratio.numer().bits() as i64 - ratio.denom().bits() as i64;
+    let two_k = |k: i64| BigFraction::from(BigUint::from(2u8).pow(k.unsigned_abs() as u32));
+    let mut m = match k >= 0 {
+        true => x.clone() / two_k(k),
+        false => x.clone() * two_k(k),
+    };
+    if m < BigFraction::from(1) {
+        m *= BigFraction::from(2);
+        k -= 1;
+    }
+    let work = digits + GUARD + k.unsigned_abs().to_string().len() as u64;
+    let s = scale(work);
+    // ln(m) = 2 atanh((m-1)/(m+1)), ln(2) = 2 atanh(1/3)
+    let z = (&m - BigFraction::from(1)) / (&m + BigFraction::from(1));
+    let ln_m = atanh_fixed(&to_fixed(&z, work)?, &s) * 2;
+    let ln_2 = atanh_fixed(&(&s / 3), &s) * 2;
+    let res = ln_m + ln_2 * k;
+    Some(from_fixed(&(res / scale(work - digits)), digits))
+}
+
+fn sin_cos(x: &BigFraction, digits: u64, cos: bool) -> Option<BigFraction> {
+    // x is reduced to [0, 2pi), which needs pi with as many more digits as x has
+    let work = digits + GUARD + int_digits(x);
+    let s = scale(work);
+    let x = to_fixed(x, work)?;
+    let two_pi = pi_fixed(&s) * 2;
+    let x = ((x % &two_pi) + &two_pi) % &two_pi;
+    let res = sin_cos_fixed(&x, &s, cos);
+    Some(from_fixed(&(res / scale(work - digits)), digits))
+}
+
+pub fn sin(x: &BigFraction, digits: u64) -> Option<BigFraction> {
+    sin_cos(x, digits, false)
+}
+
+pub fn cos(x: &BigFraction, digits: u64) -> Option<BigFraction> {
+    sin_cos(x, digits, true)
+}
+
+/// n-th root of num, truncated to the given decimal digits
+pub fn root(num: &BigFraction, n: u32, digits: u64) -> Option<BigFraction> {
+    if n == 0 || (num.is_sign_negative() && !num.is_zero() && n.is_multiple_of(2)) {
+        return None;
+    }
+    if n > MAX_ROOT {
+        // the integer root would be extracted from a number with digits * n digits
+        let res = pow(&num.abs(), &BigFraction::new(1u8, n), digits)?;
+        return Some(if num.is_sign_negative() { -res } else { res });
+    }
+    let int = to_fixed(num, digits * n as u64)?;
+    let res = int.magnitude().nth_root(n);
+    match int.is_negative() {
+        true => Some(from_fixed(&-BigInt::from(res), digits)),
+        false => Some(from_fixed(&BigInt::from(res), digits)),
+    }
+}
+
+pub fn pow(base: &BigFraction, power: &BigFraction, digits: u64) -> Option<BigFraction> {
+    let ratio = algs::to_ratio(power)?;
+    let root_n = ratio.denom().to_u32().filter(|n| *n <= MAX_ROOT);
+    let bits = |base: Ratio<BigUint>| base.numer().bits() + base.denom().bits();
+    let small = |numer: &i32| {
+        algs::to_ratio(base)
+            .is_some_and(|base| bits(base) * numer.unsigned_abs() as u64 <= MAX_POW_BITS)
+    };
+    let numer = algs::numer(power).and_then(|n| algs::to_i32(&n));
+    if let (Some(n), Some(numer)) = (root_n, numer.filter(small)) {
+        let base = algs::exp(base, &BigFraction::from(numer)).ok()?;
+        return root(&base, n, digits);
+    }
+    if base.is_zero() {
+        return power.is_sign_positive().then(BigFraction::zero);
+    }
+    if base.is_sign_negative() {
+        // only odd roots of negative numbers are real, negative if the numerator is odd too
+        if ratio.denom().is_even() {
+            return None;
+        }
+        let res = pow(&base.abs(), power, digits)?;
+        return Some(if ratio.numer().is_odd() { -res } else { res });
+    }
+    // base^power = e^(power * ln(base)), whose error is multiplied by power and by the
+    // result, which has about estimate / ln(10) < estimate / 2 integer digits
+    let estimate = to_fixed(&(ln(base, int_digits(power) + 1)? * power.clone()), 0)?;
+    if estimate.abs() > BigInt::from(MAX_EXP) {
+        return None;
+    }
+    let extra = estimate.abs().to_u64()? / 2 + int_digits(power) + GUARD;
+    exp(&(ln(base, digits + extra)? * power.clone()), digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn assert_digits(actual: Option<BigFraction>, expected: &str) {
+        let digits = expected.len() - expected.find('.').unwrap() - 1;
+        let actual = actual.map(|num| truncate(&num, digits as u64));
+        assert_eq!(actual, BigFraction::from_str(expected).ok());
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_digits(Some(pi(40)), "3.1415926535897932384626433832795028841971");
+        assert_digits(Some(e(40)), "2.7182818284590452353602874713526624977572");
+        assert_digits(Some(phi(40)), "1.6180339887498948482045868343656381177203");
+        assert_digits(Some(tau(40)), "6.2831853071795864769252867665590057683943");
+    }
+
+    #[test]
+    fn test_functions() {
+        let two = BigFraction::from(2);
+        let third = BigFraction::new(1u8, 3u8);
+        assert_digits(root(&two, 2, 30), "1.414213562373095048801688724209");
+        assert_digits(pow(&two, &third, 30), "1.259921049894873164767210607278");
+        assert_digits(ln(&two, 30), "0.693147180559945309417232121458");
+        assert_digits(
+            ln(&BigFraction::new(1u8, 10u8), 30),
+            "-2.302585092994045684017991454684",
+        );
+        assert_digits(
+            exp(&BigFraction::from(-10), 30),
+            "0.000045399929762484851535591515",
+        );
+        assert_digits(sin(&two, 30), "0.909297426825681695396019865911");
+        assert_digits(
+            cos(&BigFraction::from(100), 30),
+            "0.862318872287683934101938513950",
+        );
+        let pi_30 = BigFraction::from_str("3.14159265358979323846").unwrap();
+        assert_digits(pow(&two, &pi_30, 20), "8.82497782707628762384");
+        assert_eq!(ln(&BigFraction::from(-1), 10), None);
+        assert_eq!(root(&BigFraction::from(-4), 2, 10), None);
+        assert_digits(root(&two, 100_000, 20), "1.00000693149582830565");
+        assert_digits(root(&-two, 100_001, 20), "-1.00000693142651380028");
+        assert_eq!(
+            root(&BigFraction::zero(), 100_000, 20),
+            Some(BigFraction::zero())
+        );
+    }
+
+    #[test]
+    fn test_big_results() {
+        let num = |str: &str| BigFraction::from_str(str).unwrap();
+        assert_digits(
+            exp(&BigFraction::from(231), 8),
+            "20990622567530634724568039312619468571424633280638988147410534168412274176603982054649485766566572080.76697234",
+        );
+        assert_digits(
+            pow(&BigFraction::from(3), &num("123.4567"), 8),
+            "80133775971750676305701175584592932171144220414499521604154.00211290",
+        );
+        assert_digits(
+            pow(&BigFraction::from(-3), &BigFraction::new(12345u16, 67u8), 8),
+            "-8154014610389077799662759885363052669839129256991390663102467128142681952610983553131325.43096810",
+        );
+        let power = BigFraction::new(1_000_000_000u32, 3u8);
+        assert_eq!(pow(&BigFraction::from(2), &power, 8), None);
+        assert_eq!(exp(&BigFraction::from(MAX_EXP + 1), 8), None);
+    }
+}
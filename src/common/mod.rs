@@ -1,9 +1,12 @@
 use std::{
+    ffi::OsString,
     fmt::{self, Display},
     io::{self, IsTerminal},
+    path::PathBuf,
 };
 
 pub mod algs;
+pub mod approx;
 
 pub enum Color {
     /// success
@@ -43,3 +46,37 @@ pub fn fmt<T: fmt::Display>(items: &[T], sep: Option<&str>) -> String {
 pub fn convert<T: Clone, F: From<T>>(items: &[T]) -> Vec<F> {
     items.iter().map(|t| F::from(t.clone())).collect()
 }
+
+/// path of the calculator file name in the xdg directory, as "$XDG_DATA_HOME/calculator/name",
+/// or in home/default if xdg isn't an absolute path, as "~/.local/share/calculator/name"
+pub fn xdg_file(
+    xdg: Option<OsString>,
+    home: Option<OsString>,
+    default: &str,
+    name: &str,
+) -> Option<PathBuf> {
+    let dir = match xdg.map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        Some(dir) => dir,
+        None => PathBuf::from(home.filter(|home| !home.is_empty())?).join(default),
+    };
+    Some(dir.join("calculator").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xdg_file() {
+        let os = |str: &str| Some(OsString::from(str));
+        let file = |xdg, home| xdg_file(xdg, home, ".local/share", "history");
+        let path = |str: &str| Some(PathBuf::from(str));
+        let expected = path("/data/calculator/history");
+        assert_eq!(file(os("/data"), os("/home/me")), expected);
+        let expected = path("/home/me/.local/share/calculator/history");
+        assert_eq!(file(os("data"), os("/home/me")), expected);
+        assert_eq!(file(None, os("/home/me")), expected);
+        assert_eq!(file(None, os("")), None);
+        assert_eq!(file(None, None), None);
+    }
+}
@@ -0,0 +1,493 @@
+use super::{
+    env::Env,
+    error::{Error, SolveErr},
+    function::{self, Constant, UserFunction},
+    token::{BinaryOp, EndBlock, Span, StartBlock, Token, UnaryOpLeft, UnaryOpRight},
+    value::Value,
+};
+use crate::common::{self, algs, approx};
+use fraction::{BigFraction, Zero};
+use std::{fmt::Display, ops::Neg};
+
+/// maximum levels of a parsed expression, deeper ones would overflow the stack while solved
+pub const MAX_NESTING: usize = 128;
+/// maximum levels reached while solving, including the bodies of user defined functions
+const MAX_DEPTH: usize = MAX_NESTING * 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(BigFraction),
+    /// approximation of an irrational number
+    Approx(BigFraction),
+    Variable(String),
+    /// "( expr )" or "| expr |"
+    Block(StartBlock, Box<Expr>),
+    /// "name( expr , ... )"
+    Call(String, Vec<Expr>),
+    UnaryLeft(UnaryOpLeft, Box<Expr>),
+    UnaryRight(UnaryOpRight, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn num(&self) -> Option<&BigFraction> {
+        match self {
+            Expr::Number(num) | Expr::Approx(num) => Some(num),
+            _ => None,
+        }
+    }
+
+    /// tokens which, parsed again, produce the same expression
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.push_tokens(&mut tokens, false);
+        tokens
+    }
+
+    /// pushes the tokens of the expression, if readable with brackets around the numbers
+    /// which would otherwise be read as part of another operation, as "2 ^ 1/2" or "-2 ^ 2"
+    fn push_tokens(&self, tokens: &mut Vec<Token>, readable: bool) {
+        // operands which are negative or fraction numbers, if that changes how they are read
+        let operand = |expr: &Expr, tokens: &mut Vec<Token>, negative: bool| {
+            let ambiguous = match expr {
+                Expr::Number(num) => !num.fract().is_zero() || (negative && num.is_sign_negative()),
+                Expr::Approx(num) => negative && num.is_sign_negative(),
+                _ => false,
+            };
+            match readable && ambiguous {
+                true => Expr::Block(StartBlock::Bracket, Box::new(expr.clone()))
+                    .push_tokens(tokens, readable),
+                false => expr.push_tokens(tokens, readable),
+            }
+        };
+        match self {
+            Expr::Number(num) => tokens.push(Token::Number(num.clone())),
+            Expr::Approx(num) => tokens.push(Token::Approx(num.clone())),
+            Expr::Variable(name) => tokens.push(Token::Identifier(name.clone())),
+            Expr::Block(start, expr) => {
+                tokens.push(Token::from(start.clone()));
+                expr.push_tokens(tokens, readable);
+                tokens.push(Token::from(start.corrisp()));
+            }
+            Expr::Call(name, args) => {
+                tokens.push(Token::from(StartBlock::Call(name.clone())));
+                for (index, arg) in args.iter().enumerate() {
+                    if index != 0 {
+                        tokens.push(Token::Separator);
+                    }
+                    arg.push_tokens(tokens, readable);
+                }
+                tokens.push(Token::from(EndBlock::Bracket));
+            }
+            Expr::UnaryLeft(op, expr) => {
+                tokens.push(Token::from(op.clone()));
+                expr.push_tokens(tokens, readable);
+            }
+            Expr::UnaryRight(op, expr) => {
+                // "1/2 !" is "1 / (2 !)" and "-2 !" is "-(2 !)"
+                operand(expr, tokens, true);
+                tokens.push(Token::from(op.clone()));
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                // "-2 ^ 2" is "-(2 ^ 2)", "1/2 ^ 2" is "1 / (2 ^ 2)" and "6 / 1/2" is "(6 / 1) / 2"
+                match op {
+                    BinaryOp::Exp => operand(lhs, tokens, true),
+                    _ => lhs.push_tokens(tokens, readable),
+                }
+                tokens.push(Token::from(op.clone()));
+                match op {
+                    BinaryOp::Exp | BinaryOp::Div | BinaryOp::Mod => operand(rhs, tokens, false),
+                    _ => rhs.push_tokens(tokens, readable),
+                }
+            }
+        }
+    }
+
+    /// the expression as an explanation step, with its approximations rounded to prec digits
+    pub fn fmt_step(&self, prec: Option<u64>) -> String {
+        let mut tokens = Vec::new();
+        self.push_tokens(&mut tokens, true);
+        let tokens = tokens.into_iter().map(|token| match (token, prec) {
+            (Token::Approx(num), Some(prec)) => Token::Approx(approx::round(&num, prec)),
+            (token, _) => token,
+        });
+        common::fmt(&tokens.collect::<Vec<_>>(), None)
+    }
+
+    /// number of tokens of the expression, without building them
+    pub fn token_count(&self) -> usize {
+        let children = self
+            .children()
+            .iter()
+            .map(|child| child.token_count())
+            .sum::<usize>();
+        match self {
+            Expr::Number(_) | Expr::Approx(_) | Expr::Variable(_) => 1,
+            Expr::Block(..) => children + 2,
+            // name and closing bracket, with a separator between each argument
+            Expr::Call(_, args) => children + args.len().max(1) + 1,
+            Expr::UnaryLeft(..) | Expr::UnaryRight(..) | Expr::Binary(..) => children + 1,
+        }
+    }
+
+    /// index of the first token of each child, in the tokens of the expression
+    fn children_offsets(&self) -> Vec<usize> {
+        let mut offset = match self {
+            Expr::Block(..) | Expr::Call(..) | Expr::UnaryLeft(..) => 1,
+            _ => 0,
+        };
+        let mut offsets = Vec::new();
+        for child in self.children() {
+            offsets.push(offset);
+            // children are separated by an operator or a separator
+            offset += child.token_count() + 1;
+        }
+        offsets
+    }
+
+    fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Number(_) | Expr::Approx(_) | Expr::Variable(_) => vec![],
+            Expr::Block(_, expr) | Expr::UnaryLeft(_, expr) | Expr::UnaryRight(_, expr) => {
+                vec![expr.as_ref()]
+            }
+            Expr::Call(_, args) => args.iter().collect(),
+            Expr::Binary(_, lhs, rhs) => vec![lhs.as_ref(), rhs.as_ref()],
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Number(_) | Expr::Approx(_) | Expr::Variable(_) => vec![],
+            Expr::Block(_, expr) | Expr::UnaryLeft(_, expr) | Expr::UnaryRight(_, expr) => {
+                vec![expr.as_mut()]
+            }
+            Expr::Call(_, args) => args.iter_mut().collect(),
+            Expr::Binary(_, lhs, rhs) => vec![lhs.as_mut(), rhs.as_mut()],
+        }
+    }
+
+    /// copy of the expression, with every variable in params replaced by its argument
+    pub fn expand(&self, params: &[String], args: &[Value]) -> Expr {
+        let mut expr = self.clone();
+        expr.substitute(params, args);
+        expr
+    }
+
+    fn substitute(&mut self, params: &[String], args: &[Value]) {
+        if let Expr::Variable(name) = self {
+            if let Some(index) = params.iter().position(|param| param == name) {
+                if let Some(arg) = args.get(index) {
+                    *self = Expr::from(arg.clone());
+                }
+            }
+        }
+        for child in self.children_mut() {
+            child.substitute(params, args);
+        }
+    }
+
+    /// solves the first operation whose operands are all numbers,
+    /// returns false if the expression is already a number
+    /// spans has the range of each token, the ones of the solved operation are joined
+    pub fn step(
+        &mut self,
+        env: &Env,
+        digits: Option<u64>,
+        spans: &mut Vec<Span>,
+    ) -> Result<bool, Error> {
+        self.step_at(env, digits, spans, 0)
+    }
+
+    fn step_at(
+        &mut self,
+        env: &Env,
+        digits: Option<u64>,
+        spans: &mut Vec<Span>,
+        offset: usize,
+    ) -> Result<bool, Error> {
+        if self.num().is_some() {
+            return Ok(false);
+        }
+        let offsets = self.children_offsets();
+        for (child, child_offset) in self.children_mut().into_iter().zip(offsets) {
+            if child.step_at(env, digits, spans, offset + child_offset)? {
+                return Ok(true);
+            }
+        }
+        let range = offset..offset + self.token_count();
+        let span = spans.get(range.clone()).unwrap_or_default();
+        *self = Expr::from(self.eval(env, digits, span)?);
+        if let Some(span) = Span::join(span) {
+            spans.splice(range, [span]);
+        }
+        Ok(true)
+    }
+
+    /// value of the expression, irrational results are approximated
+    /// with the given decimal digits, or are an error if there are none.
+    /// spans has the range of each token, used to locate errors
+    pub fn eval(&self, env: &Env, digits: Option<u64>, spans: &[Span]) -> Result<Value, Error> {
+        self.eval_at(env, digits, 0, spans)
+    }
+
+    fn eval_at(
+        &self,
+        env: &Env,
+        digits: Option<u64>,
+        depth: usize,
+        spans: &[Span],
+    ) -> Result<Value, Error> {
+        // errors of this operation are located at its tokens, unless a child caused them
+        self.eval_node(env, digits, depth, spans)
+            .map_err(|err| err.at(Span::join(spans)))
+    }
+
+    fn eval_node(
+        &self,
+        env: &Env,
+        digits: Option<u64>,
+        depth: usize,
+        spans: &[Span],
+    ) -> Result<Value, Error> {
+        let children_spans = self
+            .children()
+            .into_iter()
+            .zip(self.children_offsets())
+            .map(|(child, offset)| {
+                spans
+                    .get(offset..offset + child.token_count())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let value = match self {
+            Expr::Number(num) => Value::exact(num.clone()),
+            Expr::Approx(num) => Value::approx(num.clone()),
+            Expr::Variable(name) => match (env.get(name), Constant::from_name(name)) {
+                (Some(value), _) => value.clone(),
+                (None, Some(constant)) => constant.value(digits)?,
+                (None, None) => Err(SolveErr::UnknownVariable(name.clone()))?,
+            },
+            Expr::Block(start, expr) => {
+                let value = expr.eval_at(env, digits, depth + 1, children_spans[0])?;
+                match start {
+                    StartBlock::Abs => Value {
+                        num: value.num.abs(),
+                        ..value
+                    },
+                    _ => value,
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .zip(&children_spans)
+                    .map(|(arg, spans)| arg.eval_at(env, digits, depth + 1, spans))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let exact = args.iter().all(|arg| arg.exact);
+                let value = match env.func(name) {
+                    Some(func) => call_user(name, func, &args, env, digits, depth)?,
+                    None => {
+                        let nums = args.iter().map(|arg| &arg.num).collect::<Vec<_>>();
+                        function::call(name, &nums, digits)?
+                    }
+                };
+                Value {
+                    exact: exact && value.exact,
+                    ..value
+                }
+            }
+            Expr::UnaryLeft(op, expr) => {
+                let value = expr.eval_at(env, digits, depth + 1, children_spans[0])?;
+                let num = match op {
+                    UnaryOpLeft::Neg => value.num.neg(),
+                    UnaryOpLeft::Pos => value.num,
+                    UnaryOpLeft::Derang => algs::dereng(&value.num)?,
+                };
+                Value { num, ..value }
+            }
+            Expr::UnaryRight(op, expr) => {
+                let value = expr.eval_at(env, digits, depth + 1, children_spans[0])?;
+                let num = match op {
+                    UnaryOpRight::Fact => algs::fact(&value.num)?,
+                };
+                Value { num, ..value }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval_at(env, digits, depth + 1, children_spans[0])?;
+                let rhs = rhs.eval_at(env, digits, depth + 1, children_spans[1])?;
+                let nums = [&lhs.num, &rhs.num];
+                let exact = lhs.exact && rhs.exact;
+                match op {
+                    BinaryOp::Add => Value {
+                        num: nums[0] + nums[1],
+                        exact,
+                    },
+                    BinaryOp::Sub => Value {
+                        num: nums[0] - nums[1],
+                        exact,
+                    },
+                    BinaryOp::Mul => Value {
+                        num: nums[0] * nums[1],
+                        exact,
+                    },
+                    BinaryOp::Mod => Value {
+                        num: calculate(&nums, op)?,
+                        exact,
+                    },
+                    BinaryOp::Div => Value {
+                        num: calculate(&nums, op)?,
+                        exact,
+                    },
+                    BinaryOp::Exp => {
+                        let value = power(&nums, rhs.exact, digits)?;
+                        Value {
+                            exact: exact && value.exact,
+                            ..value
+                        }
+                    }
+                }
+            }
+        };
+        // approximations are kept to the given digits, so they don't grow at every operation
+        match (value.exact, digits) {
+            (false, Some(digits)) => Ok(Value::approx(approx::truncate(&value.num, digits))),
+            _ => Ok(value),
+        }
+    }
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        match value.exact {
+            true => Expr::Number(value.num),
+            false => Expr::Approx(value.num),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.fmt_step(None))
+    }
+}
+
+fn call_user(
+    name: &str,
+    func: &UserFunction,
+    args: &[Value],
+    env: &Env,
+    digits: Option<u64>,
+    depth: usize,
+) -> Result<Value, Error> {
+    if !func.arity().accepts(args.len()) {
+        Err(SolveErr::WrongArity(
+            name.to_string(),
+            func.arity(),
+            args.len(),
+        ))?;
+    }
+    if depth >= MAX_DEPTH {
+        Err(SolveErr::RecursionLimit(name.to_string()))?;
+    }
+    // the body isn't part of the line, so its errors are located at the call
+    func.expand(args).eval_at(env, digits, depth + 1, &[])
+}
+
+/// exact power if rational, its approximation otherwise
+fn power(nums: &[&BigFraction], exact_exp: bool, digits: Option<u64>) -> Result<Value, Error> {
+    let illegal = || {
+        let vec = vec![
+            Token::from(nums[0].clone()),
+            Token::from(BinaryOp::Exp),
+            Token::from(nums[1].clone()),
+        ];
+        SolveErr::OperIllegalValues(vec)
+    };
+    // approximated exponents have too big denominators to be solved exactly
+    let digits = match digits {
+        Some(digits) if !exact_exp => digits,
+        _ => match (algs::exp(nums[0], nums[1]), digits) {
+            (Err(Error::Solve(SolveErr::IrrationalResult(_))), Some(digits)) => digits,
+            (res, _) => return res.map(Value::exact),
+        },
+    };
+    match approx::pow(nums[0], nums[1], digits) {
+        Some(num) => Ok(Value::approx(num)),
+        None => Err(illegal())?,
+    }
+}
+
+fn calculate(nums: &[&BigFraction], op: &BinaryOp) -> Result<BigFraction, Error> {
+    match op {
+        BinaryOp::Mod | BinaryOp::Div => {
+            if nums[1].is_zero() {
+                let vec = vec![
+                    Token::from(nums[0].clone()),
+                    Token::from(op.clone()),
+                    Token::from(nums[1].clone()),
+                ];
+                Err(SolveErr::OperIllegalValues(vec))?;
+            }
+        }
+        _ => unreachable!(),
+    }
+    match op {
+        BinaryOp::Mod => Ok(nums[0] % nums[1]),
+        BinaryOp::Div => Ok(nums[0] / nums[1]),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(n: i64) -> Box<Expr> {
+        Box::new(Expr::Number(BigFraction::from(n)))
+    }
+
+    #[test]
+    fn test_step() -> Result<(), Error> {
+        let env = Env::default();
+        // | 1 - 3 | * ( 2 + 4 )
+        let block1 = Expr::Binary(BinaryOp::Sub, num(1), num(3));
+        let block2 = Expr::Binary(BinaryOp::Add, num(2), num(4));
+        let mut expr = Expr::Binary(
+            BinaryOp::Mul,
+            Box::new(Expr::Block(StartBlock::Abs, Box::new(block1))),
+            Box::new(Expr::Block(StartBlock::Bracket, Box::new(block2))),
+        );
+        let mut steps = vec![expr.to_string()];
+        while expr.step(&env, None, &mut vec![])? {
+            steps.push(expr.to_string());
+        }
+        let expected = [
+            "| 1 - 3 | * ( 2 + 4 )",
+            "| -2 | * ( 2 + 4 )",
+            "2 * ( 2 + 4 )",
+            "2 * ( 6 )",
+            "2 * 6",
+            "12",
+        ];
+        assert_eq!(steps, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand() {
+        let params = [String::from("x")];
+        let body = Expr::Binary(
+            BinaryOp::Mul,
+            Box::new(Expr::Variable(String::from("x"))),
+            Box::new(Expr::Variable(String::from("y"))),
+        );
+        let expected = Expr::Binary(
+            BinaryOp::Mul,
+            num(2),
+            Box::new(Expr::Variable(String::from("y"))),
+        );
+        let args = [Value::exact(BigFraction::from(2))];
+        assert_eq!(body.expand(&params, &args), expected);
+    }
+}
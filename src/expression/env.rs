@@ -0,0 +1,67 @@
+use super::{
+    function::{Constant, UserFunction},
+    value::Value,
+};
+use std::collections::BTreeMap;
+
+/// name of the variable holding the last result
+pub const ANS: &str = "ans";
+/// prefix of the variables holding numbered results ("$1", "$2", ...)
+pub const HISTORY_PREFIX: char = '$';
+
+/// values which persist between resolved expressions
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    /// variables created with "name = expr"
+    vars: BTreeMap<String, Value>,
+    /// results of every resolved expression, oldest first
+    history: Vec<Value>,
+    /// functions created with "name(param, ...) = expr"
+    funcs: BTreeMap<String, UserFunction>,
+}
+
+impl Env {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        if name == ANS {
+            return self.history.last();
+        }
+        if let Some(index) = name.strip_prefix(HISTORY_PREFIX) {
+            let index = index.parse::<usize>().ok()?;
+            return self.history.get(index.checked_sub(1)?);
+        }
+        self.vars.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.vars.iter()
+    }
+
+    pub fn func(&self, name: &str) -> Option<&UserFunction> {
+        self.funcs.get(name)
+    }
+
+    pub fn set_func(&mut self, name: &str, func: UserFunction) {
+        self.funcs.insert(name.to_string(), func);
+    }
+
+    pub fn funcs(&self) -> impl Iterator<Item = (&String, &UserFunction)> {
+        self.funcs.iter()
+    }
+
+    pub fn push_result(&mut self, value: Value) {
+        self.history.push(value);
+    }
+
+    pub fn history(&self) -> &[Value] {
+        &self.history
+    }
+
+    /// names which cannot be assigned, as they refer to previous results or constants
+    pub fn is_reserved(name: &str) -> bool {
+        name == ANS || name.starts_with(HISTORY_PREFIX) || Constant::from_name(name).is_some()
+    }
+}
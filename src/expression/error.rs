@@ -1,8 +1,9 @@
 #![allow(dead_code, unused)]
 
 use super::{
+    function::Arity,
     solver::CheckRules,
-    token::{Token, TokenType},
+    token::{Span, Token, TokenType},
 };
 use crate::common;
 use std::fmt::Display;
@@ -12,12 +13,21 @@ pub enum Error {
     Parse(ParseErr),
     Check(CheckErr),
     Solve(SolveErr),
+    /// error caused by the given range of the line
+    Spanned(Box<Error>, Span),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ParseErr {
     InvalidNumber(String),
+    /// number, index of the digit not valid in the radix, radix
+    InvalidDigit(String, usize, u32),
     InvalidToken(String),
+    InvalidAssignment(String),
+    /// decimal number followed by the digits of a repetend which is never closed, as "0.(3"
+    IncompleteRepetend(String),
+    /// maximum levels of nesting
+    TooNested(usize),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -30,6 +40,117 @@ pub enum CheckErr {
 pub enum SolveErr {
     ExprWithNoResult(Vec<Token>),
     OperIllegalValues(Vec<Token>),
+    IrrationalResult(Vec<Token>),
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArity(String, Arity, usize),
+    RecursionLimit(String),
+}
+
+impl Error {
+    /// error caused by the given range, unless a more precise one is already known
+    pub fn at(self, span: Option<Span>) -> Self {
+        match (self, span) {
+            (err @ Error::Spanned(..), _) | (err, None) => err,
+            (err, Some(span)) => Error::Spanned(Box::new(err), span),
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Spanned(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// error without the range which caused it
+    pub fn unspanned(self) -> Self {
+        match self {
+            Error::Spanned(err, _) => *err,
+            err => err,
+        }
+    }
+
+    /// carets under the range of line which caused the error: "1 + 5 / 0" => "    ^^^^^"
+    pub fn underline(&self, line: &str) -> Option<String> {
+        let span = self.span()?;
+        let start = line.get(..span.start)?.chars().count();
+        let len = line.get(span.start..span.end)?.chars().count().max(1);
+        Some(format!("{}{}", " ".repeat(start), "^".repeat(len)))
+    }
+
+    /// name of the kind of error, as "ParseErr"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Parse(_) => "ParseErr",
+            Error::Check(_) => "CheckErr",
+            Error::Solve(_) => "SolveErr",
+            Error::Spanned(err, _) => err.kind(),
+        }
+    }
+
+    /// name of the error, as "InvalidToken"
+    pub fn variant(&self) -> &'static str {
+        match self {
+            Error::Parse(err) => match err {
+                ParseErr::InvalidNumber(_) => "InvalidNumber",
+                ParseErr::InvalidDigit(..) => "InvalidDigit",
+                ParseErr::InvalidToken(_) => "InvalidToken",
+                ParseErr::InvalidAssignment(_) => "InvalidAssignment",
+                ParseErr::IncompleteRepetend(_) => "IncompleteRepetend",
+                ParseErr::TooNested(_) => "TooNested",
+            },
+            Error::Check(err) => match err {
+                CheckErr::UnbalancedBlocks(_) => "UnbalancedBlocks",
+                CheckErr::BrokenCheckRule(_) => "BrokenCheckRule",
+            },
+            Error::Solve(err) => match err {
+                SolveErr::ExprWithNoResult(_) => "ExprWithNoResult",
+                SolveErr::OperIllegalValues(_) => "OperIllegalValues",
+                SolveErr::IrrationalResult(_) => "IrrationalResult",
+                SolveErr::UnknownVariable(_) => "UnknownVariable",
+                SolveErr::UnknownFunction(_) => "UnknownFunction",
+                SolveErr::WrongArity(..) => "WrongArity",
+                SolveErr::RecursionLimit(_) => "RecursionLimit",
+            },
+            Error::Spanned(err, _) => err.variant(),
+        }
+    }
+
+    /// the tokens which caused the error, or the names and numbers in their place
+    pub fn tokens(&self) -> Vec<String> {
+        let strs = |tokens: &[Token]| tokens.iter().map(Token::to_string).collect();
+        match self {
+            Error::Parse(err) => match err {
+                ParseErr::InvalidNumber(str)
+                | ParseErr::InvalidDigit(str, ..)
+                | ParseErr::InvalidToken(str)
+                | ParseErr::InvalidAssignment(str)
+                | ParseErr::IncompleteRepetend(str) => vec![str.clone()],
+                ParseErr::TooNested(_) => vec![],
+            },
+            Error::Check(err) => match err {
+                CheckErr::UnbalancedBlocks(tokens) => strs(tokens),
+                CheckErr::BrokenCheckRule(_) => vec![],
+            },
+            Error::Solve(err) => match err {
+                SolveErr::ExprWithNoResult(tokens)
+                | SolveErr::OperIllegalValues(tokens)
+                | SolveErr::IrrationalResult(tokens) => strs(tokens),
+                SolveErr::UnknownVariable(name)
+                | SolveErr::UnknownFunction(name)
+                | SolveErr::WrongArity(name, ..)
+                | SolveErr::RecursionLimit(name) => vec![name.clone()],
+            },
+            Error::Spanned(err, _) => err.tokens(),
+        }
+    }
+}
+
+impl From<Error> for Vec<Error> {
+    fn from(value: Error) -> Self {
+        vec![value]
+    }
 }
 
 impl From<ParseErr> for Error {
@@ -56,9 +177,20 @@ impl Display for SolveErr {
             SolveErr::OperIllegalValues(tokens) => {
                 format!("invalid operation '{}'", common::fmt(tokens, None))
             }
+            SolveErr::IrrationalResult(tokens) => {
+                format!("irrational result '{}'", common::fmt(tokens, None))
+            }
             SolveErr::ExprWithNoResult(tokens) => {
                 format!("expression has no results '{}'", common::fmt(tokens, None))
             }
+            SolveErr::UnknownVariable(name) => format!("unknown variable '{name}'"),
+            SolveErr::UnknownFunction(name) => format!("unknown function '{name}'"),
+            SolveErr::WrongArity(name, arity, count) => {
+                format!("function '{name}' takes {arity} arguments, but {count} were given")
+            }
+            SolveErr::RecursionLimit(name) => {
+                format!("function '{name}' exceeded the maximum recursion depth")
+            }
         };
         write!(f, "{err}")
     }
@@ -68,7 +200,17 @@ impl Display for ParseErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let err = match self {
             ParseErr::InvalidNumber(num) => format!("invalid number '{num}'"),
+            ParseErr::InvalidDigit(num, index, radix) => {
+                let digit = num.chars().nth(*index).unwrap_or_default();
+                let pos = index + 1;
+                format!("invalid digit '{digit}' in base {radix} number '{num}' at position {pos}")
+            }
             ParseErr::InvalidToken(tok) => format!("invalid token '{tok}'"),
+            ParseErr::InvalidAssignment(lhs) => format!("invalid assignment to '{lhs}'"),
+            ParseErr::IncompleteRepetend(num) => {
+                format!("incomplete repetend '{num}', which needs a ')'")
+            }
+            ParseErr::TooNested(max) => format!("expression nested over {max} levels"),
         };
         write!(f, "{err}")
     }
@@ -81,7 +223,7 @@ impl Display for CheckErr {
                 format!("unbalanced blocks '{}'", common::fmt(blocks, None))
             }
             CheckErr::BrokenCheckRule(rule) => {
-                format!("broken check rule '{rule:?}'")
+                format!("broken check rule '{rule}'")
             }
         };
         write!(f, "{err}")
@@ -94,6 +236,7 @@ impl Display for Error {
             Error::Parse(err) => format!("ParseErr: {err}"),
             Error::Check(err) => format!("CheckErr: {err}"),
             Error::Solve(err) => format!("SolveErr: {err}"),
+            Error::Spanned(err, _) => err.to_string(),
         };
         write!(f, "{err}")
     }
@@ -0,0 +1,388 @@
+use super::{
+    ast::Expr,
+    error::{Error, SolveErr},
+    token::{EndBlock, StartBlock, Token},
+    value::Value,
+};
+use crate::common::{algs, approx};
+use fraction::{BigFraction, Zero};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Builtin {
+    /// greatest common divisor: "gcd(12, 18)" => "6"
+    Gcd,
+    /// least common multiple: "lcm(4, 6)" => "12"
+    Lcm,
+    /// smallest argument: "min(1, -2)" => "-2"
+    Min,
+    /// biggest argument: "max(1, -2)" => "1"
+    Max,
+    /// absolute value: "abs(-2)" => "2"
+    Abs,
+    /// biggest integer not greater: "floor(-3/2)" => "-2"
+    Floor,
+    /// smallest integer not smaller: "ceil(-3/2)" => "-1"
+    Ceil,
+    /// nearest integer, half away from zero: "round(5/2)" => "3"
+    Round,
+    /// integer part: "trunc(-3/2)" => "-1"
+    Trunc,
+    /// sign as -1, 0 or 1: "sign(-3/2)" => "-1"
+    Sign,
+    /// numerator of the reduced fraction: "numer(-6/4)" => "-3"
+    Numer,
+    /// denominator of the reduced fraction: "denom(-6/4)" => "2"
+    Denom,
+    /// square root: "sqrt(9/4)" => "3/2"
+    Sqrt,
+    /// n-th root: "root(-8, 3)" => "-2"
+    Root,
+    /// natural logarithm: "ln(1)" => "0"
+    Ln,
+    /// power of e: "exp(0)" => "1"
+    Exp,
+    /// sine, in radians: "sin(0)" => "0"
+    Sin,
+    /// cosine, in radians: "cos(0)" => "1"
+    Cos,
+}
+
+/// irrational number usable like a variable, its value is always approximated
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Constant {
+    /// ratio of a circle's circumference to its diameter: "pi" => "3.14159..."
+    Pi,
+    /// base of the natural logarithm: "e" => "2.71828..."
+    E,
+    /// golden ratio: "phi" => "1.61803..."
+    Phi,
+    /// ratio of a circle's circumference to its radius: "tau" => "6.28318..."
+    Tau,
+}
+
+/// function defined with "name(param, ...) = expr"
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Builtin {
+    pub const ALL: [Self; 18] = [
+        Self::Gcd,
+        Self::Lcm,
+        Self::Min,
+        Self::Max,
+        Self::Abs,
+        Self::Floor,
+        Self::Ceil,
+        Self::Round,
+        Self::Trunc,
+        Self::Sign,
+        Self::Numer,
+        Self::Denom,
+        Self::Sqrt,
+        Self::Root,
+        Self::Ln,
+        Self::Exp,
+        Self::Sin,
+        Self::Cos,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|builtin| builtin.to_string() == name)
+    }
+
+    pub fn arity(&self) -> Arity {
+        match self {
+            Self::Gcd | Self::Lcm | Self::Min | Self::Max => Arity::AtLeast(1),
+            Self::Root => Arity::Exact(2),
+            _ => Arity::Exact(1),
+        }
+    }
+
+    /// result of the call, approximated with the given decimal digits if irrational
+    pub fn call(&self, args: &[&BigFraction], digits: Option<u64>) -> Result<Value, Error> {
+        let name = self.to_string();
+        let err = || SolveErr::OperIllegalValues(call_tokens(&name, args));
+        if !self.arity().accepts(args.len()) {
+            Err(SolveErr::WrongArity(name.clone(), self.arity(), args.len()))?;
+        }
+        let res = match self {
+            Self::Gcd => args[1..].iter().try_fold(args[0].abs(), |acc, num| {
+                algs::gcd(&acc, num).ok_or_else(err)
+            })?,
+            Self::Lcm => args[1..].iter().try_fold(args[0].abs(), |acc, num| {
+                algs::lcm(&acc, num).ok_or_else(err)
+            })?,
+            Self::Min => args.iter().copied().min().ok_or_else(err)?.clone(),
+            Self::Max => args.iter().copied().max().ok_or_else(err)?.clone(),
+            Self::Abs => args[0].abs(),
+            Self::Floor => args[0].floor(),
+            Self::Ceil => args[0].ceil(),
+            Self::Round => args[0].round(),
+            Self::Trunc => args[0].trunc(),
+            Self::Sign => algs::sign(args[0]),
+            Self::Numer => algs::numer(args[0]).ok_or_else(err)?,
+            Self::Denom => algs::denom(args[0]).ok_or_else(err)?,
+            Self::Sqrt => return self.root(args, 2, digits),
+            Self::Root => {
+                let n = algs::to_u64(args[1]).filter(|n| *n > 0).ok_or_else(err)?;
+                let n = u32::try_from(n).ok().ok_or_else(err)?;
+                return self.root(args, n, digits);
+            }
+            Self::Ln if args[0].is_sign_negative() || args[0].is_zero() => Err(err())?,
+            Self::Ln if *args[0] == BigFraction::from(1) => BigFraction::zero(),
+            Self::Ln => return self.approximate(args, digits, |d| approx::ln(args[0], d)),
+            Self::Exp if args[0].is_zero() => BigFraction::from(1),
+            Self::Exp => return self.approximate(args, digits, |d| approx::exp(args[0], d)),
+            Self::Sin if args[0].is_zero() => BigFraction::zero(),
+            Self::Sin => return self.approximate(args, digits, |d| approx::sin(args[0], d)),
+            Self::Cos if args[0].is_zero() => BigFraction::from(1),
+            Self::Cos => return self.approximate(args, digits, |d| approx::cos(args[0], d)),
+        };
+        Ok(Value::exact(res))
+    }
+
+    /// exact root if rational, its approximation otherwise
+    fn root(&self, args: &[&BigFraction], n: u32, digits: Option<u64>) -> Result<Value, Error> {
+        let name = self.to_string();
+        // errors raised by the underlying operation are reported as this call
+        match algs::root(args[0], n) {
+            Ok(num) => Ok(Value::exact(num)),
+            Err(Error::Solve(SolveErr::IrrationalResult(_))) => {
+                self.approximate(args, digits, |d| approx::root(args[0], n, d))
+            }
+            Err(Error::Solve(SolveErr::OperIllegalValues(_))) => {
+                Err(SolveErr::OperIllegalValues(call_tokens(&name, args)))?
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// irrational result, which can only be approximated if digits are given
+    fn approximate(
+        &self,
+        args: &[&BigFraction],
+        digits: Option<u64>,
+        approx: impl Fn(u64) -> Option<BigFraction>,
+    ) -> Result<Value, Error> {
+        let tokens = call_tokens(&self.to_string(), args);
+        match digits {
+            Some(digits) => match approx(digits) {
+                Some(num) => Ok(Value::approx(num)),
+                None => Err(SolveErr::OperIllegalValues(tokens))?,
+            },
+            None => Err(SolveErr::IrrationalResult(tokens))?,
+        }
+    }
+}
+
+impl Constant {
+    pub const ALL: [Self; 4] = [Self::Pi, Self::E, Self::Phi, Self::Tau];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|constant| constant.to_string() == name)
+    }
+
+    /// value approximated with the given decimal digits, or an error if there are none
+    pub fn value(&self, digits: Option<u64>) -> Result<Value, Error> {
+        let Some(digits) = digits else {
+            Err(SolveErr::IrrationalResult(vec![Token::Identifier(
+                self.to_string(),
+            )]))?
+        };
+        let num = match self {
+            Self::Pi => approx::pi(digits),
+            Self::E => approx::e(digits),
+            Self::Phi => approx::phi(digits),
+            Self::Tau => approx::tau(digits),
+        };
+        Ok(Value::approx(num))
+    }
+}
+
+impl UserFunction {
+    pub fn arity(&self) -> Arity {
+        Arity::Exact(self.params.len())
+    }
+
+    /// body of the function, with every parameter replaced by its argument
+    pub fn expand(&self, args: &[Value]) -> Expr {
+        self.body.expand(&self.params, args)
+    }
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+        }
+    }
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Gcd => "gcd",
+            Self::Lcm => "lcm",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Abs => "abs",
+            Self::Floor => "floor",
+            Self::Ceil => "ceil",
+            Self::Round => "round",
+            Self::Trunc => "trunc",
+            Self::Sign => "sign",
+            Self::Numer => "numer",
+            Self::Denom => "denom",
+            Self::Sqrt => "sqrt",
+            Self::Root => "root",
+            Self::Ln => "ln",
+            Self::Exp => "exp",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Pi => "pi",
+            Self::E => "e",
+            Self::Phi => "phi",
+            Self::Tau => "tau",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{n}"),
+            Arity::AtLeast(n) => write!(f, "at least {n}"),
+        }
+    }
+}
+
+pub fn call(name: &str, args: &[&BigFraction], digits: Option<u64>) -> Result<Value, Error> {
+    match Builtin::from_name(name) {
+        Some(builtin) => builtin.call(args, digits),
+        None => Err(SolveErr::UnknownFunction(name.to_string()))?,
+    }
+}
+
+pub fn call_tokens(name: &str, args: &[&BigFraction]) -> Vec<Token> {
+    let mut tokens = vec![Token::from(StartBlock::Call(name.to_string()))];
+    for (index, arg) in args.iter().enumerate() {
+        if index != 0 {
+            tokens.push(Token::Separator);
+        }
+        tokens.push(Token::Number((*arg).clone()));
+    }
+    tokens.push(Token::from(EndBlock::Bracket));
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_ints(name: &str, args: &[i64]) -> Result<BigFraction, Error> {
+        let args = args
+            .iter()
+            .map(|n| BigFraction::from(*n))
+            .collect::<Vec<_>>();
+        call_exact(name, &args.iter().collect::<Vec<_>>())
+    }
+
+    fn call_exact(name: &str, args: &[&BigFraction]) -> Result<BigFraction, Error> {
+        call(name, args, None).map(|value| value.num)
+    }
+
+    #[test]
+    fn test_call() -> Result<(), Error> {
+        let half = BigFraction::new(1u8, 2u8);
+        let neg_half = BigFraction::new_neg(3u8, 2u8);
+        assert_eq!(call_ints("gcd", &[12, -18, 30])?, BigFraction::from(6));
+        assert_eq!(call_ints("lcm", &[4, 6])?, BigFraction::from(12));
+        assert_eq!(call_ints("min", &[1, -2, 3])?, BigFraction::from(-2));
+        assert_eq!(call_ints("max", &[1, -2, 3])?, BigFraction::from(3));
+        assert_eq!(
+            call_exact("gcd", &[&half, &BigFraction::new(1u8, 3u8)])?,
+            BigFraction::new(1u8, 6u8)
+        );
+        assert_eq!(call_exact("floor", &[&neg_half])?, BigFraction::from(-2));
+        assert_eq!(call_exact("ceil", &[&neg_half])?, BigFraction::from(-1));
+        assert_eq!(call_exact("round", &[&half])?, BigFraction::from(1));
+        assert_eq!(call_exact("trunc", &[&neg_half])?, BigFraction::from(-1));
+        assert_eq!(
+            call_exact("sign", &[&BigFraction::from(0)])?,
+            BigFraction::from(0)
+        );
+        assert_eq!(call_exact("numer", &[&neg_half])?, BigFraction::from(-3));
+        assert_eq!(call_exact("denom", &[&neg_half])?, BigFraction::from(2));
+        assert_eq!(call_ints("sqrt", &[49])?, BigFraction::from(7));
+        assert_eq!(call_ints("root", &[-8, 3])?, BigFraction::from(-2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let wrong_arity = SolveErr::WrongArity(String::from("abs"), Arity::Exact(1), 2);
+        let unknown = SolveErr::UnknownFunction(String::from("foo"));
+        assert_eq!(call_ints("abs", &[1, 2]), Err(Error::Solve(wrong_arity)));
+        assert_eq!(call_ints("foo", &[1]), Err(Error::Solve(unknown)));
+        assert!(call_ints("gcd", &[]).is_err());
+        let irrational = SolveErr::IrrationalResult(call_tokens("sqrt", &[&BigFraction::from(2)]));
+        assert_eq!(call_ints("sqrt", &[2]), Err(Error::Solve(irrational)));
+        assert!(call_ints("root", &[4, 0]).is_err());
+        assert!(call_ints("ln", &[0]).is_err());
+    }
+
+    #[test]
+    fn test_approx() -> Result<(), Error> {
+        let two = BigFraction::from(2);
+        let sqrt = call("sqrt", &[&two], Some(5))?;
+        assert_eq!(sqrt, Value::approx(BigFraction::new(141421u32, 100000u32)));
+        assert_eq!(
+            call("sqrt", &[&BigFraction::from(4)], Some(5))?,
+            Value::exact(two)
+        );
+        assert_eq!(
+            call("ln", &[&BigFraction::from(1)], None)?,
+            Value::exact(BigFraction::zero())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_constant() -> Result<(), Error> {
+        let pi = Constant::Pi.value(Some(4))?;
+        let tau = Constant::from_name("tau").map(|tau| tau.value(Some(4)));
+        let irrational = SolveErr::IrrationalResult(vec![Token::Identifier(String::from("phi"))]);
+        assert_eq!(pi, Value::approx(BigFraction::new(31415u32, 10000u32)));
+        assert_eq!(
+            tau,
+            Some(Ok(Value::approx(BigFraction::new(62831u32, 10000u32))))
+        );
+        assert_eq!(Constant::Phi.value(None), Err(Error::Solve(irrational)));
+        assert_eq!(Constant::from_name("x"), None);
+        Ok(())
+    }
+}
@@ -1,25 +1,32 @@
 // #![allow(dead_code, unused)]
 
 use crate::{
-    common::{self, algs, Color},
+    common::{self, approx, Color},
     expression::{
+        ast::{self, Expr},
+        env::{self, Env},
         error::{CheckErr, Error, ParseErr, SolveErr},
-        token::{BinaryOp, EndBlock, StartBlock, Token, TokenType, UnaryOpLeft, UnaryOpRight},
+        function::{Builtin, Constant, UserFunction},
+        token::{
+            BinaryOp, EndBlock, Span, StartBlock, Token, TokenType, UnaryOpLeft, UnaryOpRight,
+        },
+        value::Value,
     },
 };
-use fraction::{BigFraction, Zero};
-use std::ops::Neg;
+use std::{fmt::Display, mem};
 
 const STA: TokenType = TokenType::StartBlock;
 const END: TokenType = TokenType::EndBlock;
-const UNL: TokenType = TokenType::UnaryOperatorLeft;
-const UNR: TokenType = TokenType::UnaryOperatorRight;
-const BIN: TokenType = TokenType::BinaryOperator;
-const NUM: TokenType = TokenType::Number;
 const POS: Token = Token::UnaryOperatorLeft(UnaryOpLeft::Pos);
 const NEG: Token = Token::UnaryOperatorLeft(UnaryOpLeft::Neg);
 const ADD: Token = Token::BinaryOperator(BinaryOp::Add);
 const SUB: Token = Token::BinaryOperator(BinaryOp::Sub);
+const MUL: Token = Token::BinaryOperator(BinaryOp::Mul);
+const DIV: Token = Token::BinaryOperator(BinaryOp::Div);
+const MOD: Token = Token::BinaryOperator(BinaryOp::Mod);
+const EXP: Token = Token::BinaryOperator(BinaryOp::Exp);
+const DER: Token = Token::UnaryOperatorLeft(UnaryOpLeft::Derang);
+const FAC: Token = Token::UnaryOperatorRight(UnaryOpRight::Fact);
 const DENY_DIV: CheckRules = CheckRules::DenyDivision;
 const DENY_MOD: CheckRules = CheckRules::DenyModule;
 const DENY_MLS: CheckRules = CheckRules::DenyMultipleSign;
@@ -27,19 +34,59 @@ const DENY_AMS: CheckRules = CheckRules::DenyAllMultipleSign;
 const DENY_EXP: CheckRules = CheckRules::DenyExponent;
 const DENY_FAC: CheckRules = CheckRules::DenyFactorial;
 const DENY_DERANG: CheckRules = CheckRules::DenyDerangement;
+const DENY_IMP: CheckRules = CheckRules::DenyImplicitProduct;
+
+/// precedence (higher binds tighter) and associativity of every operator:
+/// "-2^2" => "-(2^2)", "2^3^2" => "2^(3^2)", "-3!" => "-(3!)", "2^-1" => "2^(-1)"
+const PRECEDENCE: [(Token, usize, Assoc); 10] = [
+    (ADD, 1, Assoc::Left),
+    (SUB, 1, Assoc::Left),
+    (MUL, 2, Assoc::Left),
+    (DIV, 2, Assoc::Left),
+    (MOD, 2, Assoc::Left),
+    (POS, 3, Assoc::Right),
+    (NEG, 3, Assoc::Right),
+    (DER, 3, Assoc::Right),
+    (EXP, 4, Assoc::Right),
+    (FAC, 5, Assoc::Left),
+];
+
+/// operator denied by each of CheckRules::DENY_OP
+const DENIED: [(CheckRules, Token); 5] = [
+    (DENY_DIV, DIV),
+    (DENY_MOD, MOD),
+    (DENY_EXP, EXP),
+    (DENY_FAC, FAC),
+    (DENY_DERANG, DER),
+];
+
+/// how operators with the same precedence are grouped
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Assoc {
+    /// "a op b op c" => "(a op b) op c"
+    Left,
+    /// "a op b op c" => "a op (b op c)"
+    Right,
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum FixRules {
     /// fixes: "(expr) (expr)" => "(expr) * (expr)"
     BlockProduct,
+    /// fixes: "2 (expr)" => "2 * (expr)", "(expr) 2" => "(expr) * 2", "2 x" => "2 * x", "3! 2" => "3! * 2"
+    ImplicitProduct,
     /// fixes: "(|(" => "(|()|)"
     CloseBlocks,
 }
 impl FixRules {
-    pub const ALL: [Self; 2] = [Self::BlockProduct, Self::CloseBlocks];
+    pub const ALL: [Self; 3] = [Self::BlockProduct, Self::ImplicitProduct, Self::CloseBlocks];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.to_string() == name)
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CheckRules {
     /// deny: "++expr", "-+-+expr++expr", allow: "expr+-expr", "expr--expr"
     DenyMultipleSign,
@@ -55,9 +102,11 @@ pub enum CheckRules {
     DenyFactorial,
     /// deny: "! expr"
     DenyDerangement,
+    /// deny: "(expr) (expr)", "2 (expr)", "(expr) 2", "2 x", "3! 2", with or without their fix rule
+    DenyImplicitProduct,
 }
 impl CheckRules {
-    pub const ALL: [Self; 7] = [
+    pub const ALL: [Self; 8] = [
         DENY_MLS,
         DENY_AMS,
         DENY_DIV,
@@ -65,123 +114,474 @@ impl CheckRules {
         DENY_EXP,
         DENY_FAC,
         DENY_DERANG,
+        DENY_IMP,
     ];
     pub const DENY_OP: [Self; 5] = [DENY_DIV, DENY_MOD, DENY_EXP, DENY_FAC, DENY_DERANG];
     pub const DENY_SIGN: [Self; 2] = [DENY_MLS, DENY_AMS];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.to_string() == name)
+    }
+}
+
+impl Display for FixRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::BlockProduct => "block-product",
+            Self::ImplicitProduct => "implicit-product",
+            Self::CloseBlocks => "close-blocks",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl Display for CheckRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::DenyMultipleSign => "deny-multiple-sign",
+            Self::DenyAllMultipleSign => "deny-all-multiple-sign",
+            Self::DenyDivision => "deny-division",
+            Self::DenyModule => "deny-module",
+            Self::DenyExponent => "deny-exponent",
+            Self::DenyFactorial => "deny-factorial",
+            Self::DenyDerangement => "deny-derangement",
+            Self::DenyImplicitProduct => "deny-implicit-product",
+        };
+        write!(f, "{str}")
+    }
 }
 
 pub fn resolve(
     str: &str,
+    env: &mut Env,
+    fixes: &[FixRules],
+    checks: &[CheckRules],
+    prec: Option<u64>,
+    explain: bool,
+) -> Result<Value, Error> {
+    resolve_all(str, env, fixes, checks, prec, explain).map_err(first)
+}
+
+/// like resolve, but returns every error found while parsing, instead of only the first
+pub fn resolve_all(
+    str: &str,
+    env: &mut Env,
     fixes: &[FixRules],
     checks: &[CheckRules],
+    prec: Option<u64>,
     explain: bool,
-) -> Result<BigFraction, Error> {
-    let mut tokens = parse(str, fixes, checks)?;
-    if explain {
+) -> Result<Value, Vec<Error>> {
+    if !explain {
+        let (name, rhs) = parse_assignment(str)?;
+        let (expr, spans) = parse_at(rhs, str.len() - rhs.len(), fixes, checks)?;
+        return Ok(solve(expr, spans, name, env, prec, None)?);
+    }
+    let mut steps = vec![];
+    let res = resolve_steps(str, env, fixes, checks, prec, &mut steps);
+    if !steps.is_empty() {
         let title = common::color(&Color::TIT, "Explanation:");
-        println!("{title}\n{}", common::fmt(&tokens, None));
+        println!("{title}\n{}", steps.join("\n"));
     }
-    while solve_next(&mut tokens)? {
-        if explain {
-            println!("{}", common::fmt(&tokens, None));
+    res
+}
+
+/// like resolve_all, but pushes each step of the explanation to steps, instead of printing them,
+/// the steps before an error included
+pub fn resolve_steps(
+    str: &str,
+    env: &mut Env,
+    fixes: &[FixRules],
+    checks: &[CheckRules],
+    prec: Option<u64>,
+    steps: &mut Vec<String>,
+) -> Result<Value, Vec<Error>> {
+    let (name, rhs) = parse_assignment(str)?;
+    let (expr, spans) = parse_at(rhs, str.len() - rhs.len(), fixes, checks)?;
+    Ok(solve(expr, spans, name, env, prec, Some(steps))?)
+}
+
+/// solves the parsed expression, storing the result as name if given,
+/// and pushing each step to steps if given
+fn solve(
+    mut expr: Expr,
+    mut spans: Vec<Span>,
+    name: Option<String>,
+    env: &mut Env,
+    prec: Option<u64>,
+    steps: Option<&mut Vec<String>>,
+) -> Result<Value, Error> {
+    let digits = prec.map(|prec| prec + approx::GUARD);
+    let res = match steps {
+        Some(steps) => {
+            steps.push(expr.fmt_step(prec));
+            while expr.step(env, digits, &mut spans)? {
+                // removing the brackets of "( -2 ) ^ 2" doesn't change how it's shown
+                let step = expr.fmt_step(prec);
+                if steps.last() != Some(&step) {
+                    steps.push(step);
+                }
+            }
+            match expr {
+                Expr::Number(num) => Value::exact(num),
+                Expr::Approx(num) => Value::approx(num),
+                _ => Err(SolveErr::ExprWithNoResult(expr.tokens()))?,
+            }
+        }
+        None => expr.eval(env, digits, &spans)?,
+    };
+    let res = match (res.exact, prec) {
+        (false, Some(prec)) => Value::approx(approx::round(&res.num, prec)),
+        _ => res,
+    };
+    if let Some(name) = name {
+        env.set(&name, res.clone());
+    }
+    env.push_result(res.clone());
+    Ok(res)
+}
+
+/// stores "name(param, ...) = expr" as a function, returning its name,
+/// or returns None if str is not a function definition
+pub fn define(
+    str: &str,
+    env: &mut Env,
+    fixes: &[FixRules],
+    checks: &[CheckRules],
+) -> Result<Option<String>, Error> {
+    define_all(str, env, fixes, checks).map_err(first)
+}
+
+/// like define, but returns every error found in the definition, instead of only the first
+pub fn define_all(
+    str: &str,
+    env: &mut Env,
+    fixes: &[FixRules],
+    checks: &[CheckRules],
+) -> Result<Option<String>, Vec<Error>> {
+    let Some((lhs, rhs)) = str.split_once('=') else {
+        return Ok(None);
+    };
+    let lhs_tokens = parse_tokens(lhs)?;
+    let invalid = || invalid_assignment(lhs);
+    let (name, params) = match lhs_tokens.as_slice() {
+        [Token::StartBlock(StartBlock::Call(name)), params @ .., Token::EndBlock(EndBlock::Bracket)] => {
+            (name, params)
         }
+        [Token::StartBlock(StartBlock::Call(_)), ..] => Err(invalid())?,
+        _ => return Ok(None),
+    };
+    let params = match params {
+        [] => vec![],
+        _ => params
+            .split(|token| token == &Token::Separator)
+            .map(|param| match param {
+                [Token::Identifier(param)] if !Env::is_reserved(param) => Some(param.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?,
+    };
+    let duplicated = (1..params.len()).any(|i| params[i..].contains(&params[i - 1]));
+    if duplicated || Builtin::from_name(name).is_some() {
+        Err(invalid())?;
+    }
+
+    let (body, spans) = parse_at(rhs, lhs.len() + 1, fixes, checks)?;
+    let mut errs = Vec::new();
+    for (token, span) in body.tokens().iter().zip(spans) {
+        let err = match token {
+            Token::Identifier(var)
+                if !params.contains(var)
+                    && env.get(var).is_none()
+                    && Constant::from_name(var).is_none() =>
+            {
+                SolveErr::UnknownVariable(var.clone())
+            }
+            Token::StartBlock(StartBlock::Call(func))
+                if func != name
+                    && Builtin::from_name(func).is_none()
+                    && env.func(func).is_none() =>
+            {
+                SolveErr::UnknownFunction(func.clone())
+            }
+            _ => continue,
+        };
+        errs.push(Error::from(err).at(Some(span)));
     }
-    let e = || SolveErr::ExprWithNoResult(tokens.clone());
-    if tokens.len() != 1 {
-        return Err(Error::Solve(SolveErr::ExprWithNoResult(tokens)));
+    if !errs.is_empty() {
+        return Err(errs);
     }
-    Ok(tokens.first().ok_or_else(e)?.num().ok_or_else(e)?.clone())
+    env.set_func(name, UserFunction { params, body });
+    Ok(Some(name.clone()))
+}
+
+pub fn parse(str: &str, fixes: &[FixRules], checks: &[CheckRules]) -> Result<Expr, Error> {
+    parse_all(str, fixes, checks).map_err(first)
 }
 
-pub fn parse(str: &str, fixes: &[FixRules], checks: &[CheckRules]) -> Result<Vec<Token>, Error> {
-    let mut tokens = parse_tokens(str)?;
-    fix_tokens(&mut tokens, fixes);
-    check_rules(&tokens, checks)?;
-    Ok(tokens)
+/// like parse, but returns every parse error, unbalanced block and broken check rule
+pub fn parse_all(str: &str, fixes: &[FixRules], checks: &[CheckRules]) -> Result<Expr, Vec<Error>> {
+    Ok(parse_at(str, 0, fixes, checks)?.0)
+}
+
+/// expression of str, with the byte ranges of its tokens shifted by offset
+fn parse_at(
+    str: &str,
+    offset: usize,
+    fixes: &[FixRules],
+    checks: &[CheckRules],
+) -> Result<(Expr, Vec<Span>), Vec<Error>> {
+    let mut errs = Vec::new();
+    let (mut tokens, mut spans) = tokenize_all(str, offset, &mut errs);
+    fix_tokens(&mut tokens, &mut spans, fixes);
+    if let Err(check_errs) = check_rules(&tokens, &spans, checks) {
+        errs.extend(check_errs);
+    }
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+    Ok((build_tree(&tokens, &spans)?, spans))
+}
+
+/// first of the errors, which are never empty
+fn first(errs: Vec<Error>) -> Error {
+    let mut errs = errs.into_iter();
+    errs.next()
+        .unwrap_or(Error::Solve(SolveErr::ExprWithNoResult(Vec::new())))
+}
+
+/// splits "name = expr" into the variable name and the expression to solve
+fn parse_assignment(str: &str) -> Result<(Option<String>, &str), Error> {
+    let Some((lhs, rhs)) = str.split_once('=') else {
+        return Ok((None, str));
+    };
+    match parse_tokens(lhs)?.as_slice() {
+        [Token::Identifier(name)] if !Env::is_reserved(name) => Ok((Some(name.clone()), rhs)),
+        _ => Err(invalid_assignment(lhs))?,
+    }
+}
+
+/// error for the left side of "lhs = rhs", spanning its trimmed text
+fn invalid_assignment(lhs: &str) -> Error {
+    let start = lhs.len() - lhs.trim_start().len();
+    let span = Span::new(start, lhs.trim_end().len().max(start));
+    Error::from(ParseErr::InvalidAssignment(lhs.trim().to_string())).at(Some(span))
 }
 
 fn parse_tokens(str: &str) -> Result<Vec<Token>, Error> {
+    Ok(tokenize(str, 0)?.0)
+}
+
+/// tokens of str with their byte ranges, shifted by offset
+fn tokenize(str: &str, offset: usize) -> Result<(Vec<Token>, Vec<Span>), Error> {
+    let mut errs = Vec::new();
+    let res = tokenize_all(str, offset, &mut errs);
+    match errs.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(res),
+    }
+}
+
+/// tokens of str with their byte ranges, shifted by offset:
+/// invalid characters and numbers are pushed to errs and skipped, to keep reading the rest
+fn tokenize_all(str: &str, offset: usize, errs: &mut Vec<Error>) -> (Vec<Token>, Vec<Span>) {
     let mut acc_num = String::new();
+    let mut acc_ide = String::new();
+    // where the number or identifier being read starts
+    let mut acc_start = offset;
     let mut stack = Vec::<StartBlock>::new();
     let mut res = Vec::new();
+    let mut spans = Vec::new();
 
-    for c in str.chars() {
-        if !acc_num.is_empty() && !c.is_ascii_digit() && c != '.' && c != '_' {
-            res.push(Token::parse_num(&acc_num)?);
-            acc_num.clear();
+    let chars = str.chars().collect::<Vec<_>>();
+    for (index, (byte, c)) in str.char_indices().enumerate() {
+        let byte = byte + offset;
+        let span = Span::new(byte, byte + c.len_utf8());
+        if !acc_ide.is_empty() && !c.is_ascii_alphanumeric() && c != '_' {
+            let name = mem::take(&mut acc_ide);
+            if c == '(' {
+                stack.push(StartBlock::Call(name.clone()));
+                res.push(Token::from(StartBlock::Call(name)));
+                spans.push(Span::new(acc_start, span.end));
+                continue;
+            }
+            res.push(Token::Identifier(name));
+            spans.push(Span::new(acc_start, byte));
+        }
+        if !acc_num.is_empty() && !continues_num(&acc_num, c, &chars[index + 1..]) {
+            if incomplete_repetend(&acc_num, &chars[index..]) {
+                let num_span = Span::new(acc_start, span.end);
+                let num = str[acc_start - offset..span.end - offset].to_string();
+                errs.push(Error::from(ParseErr::IncompleteRepetend(num)).at(Some(num_span)));
+                acc_num.clear();
+            } else {
+                let num_span = Span::new(acc_start, byte);
+                match Token::parse_num(&mem::take(&mut acc_num)) {
+                    Ok(num) => {
+                        res.push(num);
+                        spans.push(num_span);
+                    }
+                    Err(err) => errs.push(err.at(Some(num_span))),
+                }
+            }
         }
         if c.is_whitespace() {
             continue;
         }
-        match c {
+        if acc_num.is_empty() && acc_ide.is_empty() {
+            acc_start = byte;
+        }
+        let token = match c {
+            _ if !acc_num.is_empty() => {
+                acc_num.push(c);
+                continue;
+            }
             '+' => match res.last() {
                 Some(Token::Number(_))
                 | Some(Token::EndBlock(_))
-                | Some(Token::UnaryOperatorRight(_)) => res.push(Token::from(BinaryOp::Add)),
-                _ => res.push(Token::from(UnaryOpLeft::Pos)),
+                | Some(Token::UnaryOperatorRight(_))
+                | Some(Token::Identifier(_)) => Token::from(BinaryOp::Add),
+                _ => Token::from(UnaryOpLeft::Pos),
             },
             '-' => match res.last() {
                 Some(Token::Number(_))
                 | Some(Token::EndBlock(_))
-                | Some(Token::UnaryOperatorRight(_)) => res.push(Token::from(BinaryOp::Sub)),
-                _ => res.push(Token::from(UnaryOpLeft::Neg)),
+                | Some(Token::UnaryOperatorRight(_))
+                | Some(Token::Identifier(_)) => Token::from(BinaryOp::Sub),
+                _ => Token::from(UnaryOpLeft::Neg),
             },
-            // '!' => res.push(Token::from(UnaryOpRight::Fact)),
             '!' => match res.last() {
                 Some(Token::Number(_))
                 | Some(Token::EndBlock(_))
-                | Some(Token::UnaryOperatorRight(_)) => res.push(Token::from(UnaryOpRight::Fact)),
-                _ => res.push(Token::from(UnaryOpLeft::Derang)),
+                | Some(Token::UnaryOperatorRight(_))
+                | Some(Token::Identifier(_)) => Token::from(UnaryOpRight::Fact),
+                _ => Token::from(UnaryOpLeft::Derang),
             },
-            '^' => res.push(Token::from(BinaryOp::Exp)),
-            '*' => res.push(Token::from(BinaryOp::Mul)),
-            '/' => res.push(Token::from(BinaryOp::Div)),
-            '%' => res.push(Token::from(BinaryOp::Mod)),
+            '^' => Token::from(BinaryOp::Exp),
+            '*' => Token::from(BinaryOp::Mul),
+            '/' => Token::from(BinaryOp::Div),
+            '%' => Token::from(BinaryOp::Mod),
             '(' => {
                 stack.push(StartBlock::Bracket);
-                res.push(Token::from(StartBlock::Bracket));
+                Token::from(StartBlock::Bracket)
             }
             ')' => match stack.last() {
-                Some(StartBlock::Bracket) => {
+                Some(StartBlock::Bracket) | Some(StartBlock::Call(_)) => {
                     stack.pop();
-                    res.push(Token::from(EndBlock::Bracket));
+                    Token::from(EndBlock::Bracket)
+                }
+                _ => {
+                    let err = CheckErr::UnbalancedBlocks(vec![Token::from(EndBlock::Bracket)]);
+                    errs.push(Error::from(err).at(Some(span)));
+                    continue;
+                }
+            },
+            ',' => match stack.last() {
+                Some(StartBlock::Call(_)) => Token::Separator,
+                _ => {
+                    errs.push(Error::from(ParseErr::InvalidToken(c.to_string())).at(Some(span)));
+                    continue;
                 }
-                _ => Err(CheckErr::UnbalancedBlocks(vec![Token::from(
-                    EndBlock::Bracket,
-                )]))?,
             },
             '|' => match stack.last() {
                 Some(StartBlock::Abs) => {
                     stack.pop();
-                    res.push(Token::from(EndBlock::Abs));
+                    Token::from(EndBlock::Abs)
                 }
                 _ => {
                     stack.push(StartBlock::Abs);
-                    res.push(Token::from(StartBlock::Abs))
+                    Token::from(StartBlock::Abs)
                 }
             },
-            '0'..='9' | '.' | '_' => acc_num.push(c),
-            _ => Err(ParseErr::InvalidToken(c.to_string()))?,
-        }
+            'a'..='z' | 'A'..='Z' | env::HISTORY_PREFIX => {
+                acc_ide.push(c);
+                continue;
+            }
+            '0'..='9' | '_' if !acc_ide.is_empty() => {
+                acc_ide.push(c);
+                continue;
+            }
+            '0'..='9' | '.' | '_' => {
+                acc_num.push(c);
+                continue;
+            }
+            _ => {
+                errs.push(Error::from(ParseErr::InvalidToken(c.to_string())).at(Some(span)));
+                continue;
+            }
+        };
+        res.push(token);
+        spans.push(span);
     }
 
+    let end = offset + str.len();
     if !acc_num.is_empty() {
-        res.push(Token::parse_num(&acc_num)?);
+        let num_span = Span::new(acc_start, end);
+        match Token::parse_num(&acc_num) {
+            Ok(num) => {
+                res.push(num);
+                spans.push(num_span);
+            }
+            Err(err) => errs.push(err.at(Some(num_span))),
+        }
+    }
+    if !acc_ide.is_empty() {
+        res.push(Token::Identifier(acc_ide));
+        spans.push(Span::new(acc_start, end));
+    }
+    (res, spans)
+}
+
+/// true if c is part of the number being read, followed by the next chars:
+/// after a radix prefix any letter is kept, so that invalid digits are reported,
+/// while "e" starts an exponent only if followed by its digits, as in "1.6e-19",
+/// and "(" starts a repetend only if followed by digits and ")", as in "0.1(6)"
+fn continues_num(acc_num: &str, c: char, next: &[char]) -> bool {
+    let exponent = || match next {
+        ['+' | '-', digit, ..] | [digit, ..] => digit.is_ascii_digit(),
+        [] => false,
+    };
+    let repetend = || match next.iter().position(|c| *c == ')') {
+        Some(end) => end > 0 && next[..end].iter().all(|c| c.is_ascii_digit() || *c == '_'),
+        None => false,
+    };
+    match (Token::radix(acc_num), c) {
+        (Some(_), _) => c.is_ascii_alphanumeric() || c == '_',
+        (None, _) if acc_num.contains('(') => !acc_num.ends_with(')'),
+        (None, '(') => acc_num.contains('.') && !acc_num.contains(['e', 'E']) && repetend(),
+        (None, '0'..='9' | '.' | '_') => true,
+        (None, 'x' | 'X' | 'b' | 'B' | 'o' | 'O') => acc_num == "0",
+        (None, 'e' | 'E') => !acc_num.contains(['e', 'E']) && exponent(),
+        (None, '+' | '-') => acc_num.ends_with(['e', 'E']),
+        _ => false,
     }
-    Ok(res)
 }
 
-fn fix_tokens(tokens: &mut Vec<Token>, rules: &[FixRules]) {
-    if rules.contains(&FixRules::BlockProduct) {
-        let rule1_pos = tokens
-            .windows(2)
-            .enumerate()
-            .filter_map(|(i, w)| (w[0].eq_tokentype(&END) && w[1].eq_tokentype(&STA)).then_some(i))
-            .rev()
-            .collect::<Vec<_>>();
-        for pos in rule1_pos {
-            tokens.insert(pos + 1, Token::BinaryOperator(BinaryOp::Mul));
+/// true if next starts a repetend of the decimal number being read which is never closed,
+/// as "0.(3", since any other "(" after a number starts a block
+fn incomplete_repetend(acc_num: &str, next: &[char]) -> bool {
+    let decimal = Token::radix(acc_num).is_none() && acc_num.contains('.');
+    match next {
+        ['(', digits @ ..] if decimal && !acc_num.contains(['(', 'e', 'E']) => {
+            !digits.is_empty() && digits.iter().all(|c| c.is_ascii_digit() || *c == '_')
         }
+        _ => false,
+    }
+}
+
+/// applies the fix rules, inserted tokens have an empty span where they are inserted
+fn fix_tokens(tokens: &mut Vec<Token>, spans: &mut Vec<Span>, rules: &[FixRules]) {
+    let product_pos = tokens
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| implied_product(&w[0], &w[1]).is_some_and(|rule| rules.contains(&rule)))
+        .map(|(i, _)| i)
+        .rev()
+        .collect::<Vec<_>>();
+    for pos in product_pos {
+        tokens.insert(pos + 1, Token::BinaryOperator(BinaryOp::Mul));
+        let end = spans[pos].end;
+        spans.insert(pos + 1, Span::new(end, end));
     }
 
     if rules.contains(&FixRules::CloseBlocks) {
@@ -189,180 +589,240 @@ fn fix_tokens(tokens: &mut Vec<Token>, rules: &[FixRules]) {
         for token in tokens.iter() {
             match token {
                 Token::StartBlock(start) => stack.push(start.clone()),
-                Token::EndBlock(end) => assert_eq!(stack.pop(), Some(end.corrisp())),
+                // unbalanced blocks are left to be reported by check_rules
+                Token::EndBlock(end) if stack.last().is_some_and(|start| start.is_corrisp(end)) => {
+                    stack.pop();
+                }
                 _ => (),
             }
         }
+        let end = spans.last().map(|span| span.end).unwrap_or_default();
         for block in stack.iter().rev() {
             tokens.push(Token::from(block.corrisp()));
+            spans.push(Span::new(end, end));
+        }
+    }
+}
+
+/// fix rule which inserts the missing product between two operands next to each other
+fn implied_product(lhs: &Token, rhs: &Token) -> Option<FixRules> {
+    let lhs_ends = [TokenType::Number, END, TokenType::UnaryOperatorRight];
+    let rhs_starts = [TokenType::Number, TokenType::Identifier, STA];
+    match (TokenType::from(lhs), TokenType::from(rhs)) {
+        (END, STA) => Some(FixRules::BlockProduct),
+        // "1 2" is more likely a typo than a product
+        (TokenType::Number, TokenType::Number) => None,
+        (lhs, rhs) if lhs_ends.contains(&lhs) && rhs_starts.contains(&rhs) => {
+            Some(FixRules::ImplicitProduct)
         }
+        _ => None,
     }
 }
 
-fn check_rules(tokens: &[Token], checks: &[CheckRules]) -> Result<(), Error> {
+/// checks every rule and block, returning all the errors found
+fn check_rules(tokens: &[Token], spans: &[Span], checks: &[CheckRules]) -> Result<(), Vec<Error>> {
+    let mut errs = Vec::new();
     let mut block_stack = Vec::<StartBlock>::new();
     let mul_sign = checks.contains(&DENY_MLS);
     let all_sign = checks.contains(&DENY_AMS);
-    let deny_div = checks.contains(&DENY_DIV);
-    let deny_mod = checks.contains(&DENY_MOD);
-    let deny_exp = checks.contains(&DENY_EXP);
-    let deny_fac = checks.contains(&DENY_FAC);
+    let denied = DENIED
+        .iter()
+        .filter(|(rule, _)| checks.contains(rule))
+        .collect::<Vec<_>>();
+
+    let broken = |rule: CheckRules, range: std::ops::Range<usize>| {
+        let span = spans.get(range).and_then(Span::join);
+        Error::from(CheckErr::BrokenCheckRule(rule)).at(span)
+    };
 
     // check rules are respected
-    for token in tokens {
-        if deny_div && token == &Token::from(BinaryOp::Div) {
-            Err(CheckErr::BrokenCheckRule(DENY_DIV))?;
-        }
-        if deny_mod && token == &Token::from(BinaryOp::Mod) {
-            Err(CheckErr::BrokenCheckRule(DENY_MOD))?;
-        }
-        if deny_exp && token == &Token::from(BinaryOp::Exp) {
-            Err(CheckErr::BrokenCheckRule(DENY_EXP))?;
-        }
-        if deny_fac && token == &Token::from(UnaryOpRight::Fact) {
-            Err(CheckErr::BrokenCheckRule(DENY_FAC))?;
+    for (i, token) in tokens.iter().enumerate() {
+        for (rule, _) in denied.iter().filter(|(_, op)| op == token) {
+            errs.push(broken(*rule, i..i + 1));
         }
     }
-    for pair in tokens.windows(2) {
+    for (i, pair) in tokens.windows(2).enumerate() {
+        // products inserted by the fix rules have an empty span
+        let inserted =
+            pair[1] == MUL && spans.get(i + 1).is_some_and(|span| span.start == span.end);
+        match (inserted, implied_product(&pair[0], &pair[1])) {
+            _ if !checks.contains(&DENY_IMP) => (),
+            (true, _) => errs.push(broken(DENY_IMP, i..i + 3)),
+            (false, Some(_)) => errs.push(broken(DENY_IMP, i..i + 2)),
+            (false, None) => (),
+        }
         if mul_sign && [POS, NEG].contains(&pair[0]) && [POS, NEG].contains(&pair[1]) {
-            Err(CheckErr::BrokenCheckRule(DENY_MLS))?;
+            errs.push(broken(DENY_MLS, i..i + 2));
         }
         if all_sign && [POS, NEG, ADD, SUB].contains(&pair[0]) && [POS, NEG].contains(&pair[1]) {
-            Err(CheckErr::BrokenCheckRule(DENY_AMS))?;
+            errs.push(broken(DENY_AMS, i..i + 2));
         }
     }
 
     // check blocks are balanced
-    for token in tokens {
+    let mut first_open = 0;
+    for (i, token) in tokens.iter().enumerate() {
         match token {
-            Token::StartBlock(start) => block_stack.push(start.clone()),
-            Token::EndBlock(end) => assert_eq!(block_stack.pop(), Some(end.corrisp())),
+            Token::StartBlock(start) => {
+                if block_stack.is_empty() {
+                    first_open = i;
+                }
+                block_stack.push(start.clone())
+            }
+            Token::EndBlock(end)
+                if block_stack
+                    .last()
+                    .is_some_and(|start| start.is_corrisp(end)) =>
+            {
+                block_stack.pop();
+            }
+            Token::EndBlock(end) => {
+                let err = CheckErr::UnbalancedBlocks(vec![Token::from(end.clone())]);
+                errs.push(Error::from(err).at(spans.get(i).copied()));
+            }
             _ => (),
         }
     }
     if !block_stack.is_empty() {
         let block_stack: Vec<Token> = common::convert(&block_stack);
-        Err(CheckErr::UnbalancedBlocks(block_stack))?
+        let span = spans.get(first_open..).and_then(Span::join);
+        errs.push(Error::from(CheckErr::UnbalancedBlocks(block_stack)).at(span));
     }
 
-    Ok(())
+    match errs.is_empty() {
+        true => Ok(()),
+        false => Err(errs),
+    }
 }
 
-pub fn solve_next(tokens: &mut Vec<Token>) -> Result<bool, Error> {
-    if let Some(index) = next_operation(tokens) {
-        let token = &tokens[index];
-        let err = || Error::Solve(SolveErr::ExprWithNoResult(tokens.to_vec()));
-        let mut nums = Vec::<&BigFraction>::new();
-        let from: usize;
-        let to: usize;
-        match TokenType::from(token) {
-            STA => {
-                nums.push(tokens[index + 1].num().ok_or_else(err)?);
-                from = index;
-                to = index + 2;
-            }
-            UNL => {
-                nums.push(tokens[index + 1].num().ok_or_else(err)?);
-                from = index;
-                to = index + 1;
-            }
-            UNR => {
-                nums.push(tokens[index - 1].num().ok_or_else(err)?);
-                from = index - 1;
-                to = index;
-            }
-            BIN => {
-                nums.push(tokens[index - 1].num().ok_or_else(err)?);
-                nums.push(tokens[index + 1].num().ok_or_else(err)?);
-                from = index - 1;
-                to = index + 1;
-            }
-            _ => unreachable!(),
+/// builds the expression tree, grouping operators as described by PRECEDENCE
+fn build_tree(tokens: &[Token], spans: &[Span]) -> Result<Expr, Error> {
+    let mut pos = 0;
+    let mut deepest = 0;
+    let expr = parse_expr(tokens, &mut pos, 0, 0, &mut deepest);
+    // the error is shown from the token where parsing stopped
+    let span = match &expr {
+        Some(_) => spans.get(pos..).and_then(Span::join),
+        None => spans.get(pos.saturating_sub(1)..).and_then(Span::join),
+    };
+    match expr {
+        _ if deepest > ast::MAX_NESTING => {
+            let span = spans.get(pos.saturating_sub(1)).copied();
+            Err(Error::from(ParseErr::TooNested(ast::MAX_NESTING)).at(span))?
         }
-        let num = match token {
-            Token::StartBlock(start) => match start {
-                StartBlock::Bracket => nums[0].clone(),
-                StartBlock::Abs => nums[0].abs(),
-            },
-            Token::UnaryOperatorLeft(unary) => match unary {
-                UnaryOpLeft::Neg => nums[0].neg(),
-                UnaryOpLeft::Pos => nums[0].clone(),
-                UnaryOpLeft::Derang => algs::dereng(nums[0])?,
-            },
-            Token::UnaryOperatorRight(unary) => match unary {
-                UnaryOpRight::Fact => algs::fact(nums[0])?,
-            },
-            Token::BinaryOperator(bin) => match bin {
-                BinaryOp::Add => nums[0] + nums[1],
-                BinaryOp::Sub => nums[0] - nums[1],
-                BinaryOp::Mul => nums[0] * nums[1],
-                BinaryOp::Mod => calculate(&nums, bin)?,
-                BinaryOp::Div => calculate(&nums, bin)?,
-                BinaryOp::Exp => algs::exp(nums[0], nums[1])?,
-            },
-            _ => unreachable!(),
+        Some(expr) if pos == tokens.len() => Ok(expr),
+        _ => Err(Error::from(SolveErr::ExprWithNoResult(tokens.to_vec())).at(span))?,
+    }
+}
+
+fn precedence(token: &Token) -> Option<(usize, Assoc)> {
+    PRECEDENCE
+        .iter()
+        .find(|(op, ..)| op == token)
+        .map(|(_, prec, assoc)| (*prec, *assoc))
+}
+
+/// parses operands joined by operators with precedence at least min_prec,
+/// depth is the level of the expression in the tree, the deepest one reached is kept,
+/// and parsing stops past MAX_NESTING, so that solving the tree can't overflow the stack
+fn parse_expr(
+    tokens: &[Token],
+    pos: &mut usize,
+    min_prec: usize,
+    depth: usize,
+    deepest: &mut usize,
+) -> Option<Expr> {
+    let mut lhs = parse_operand(tokens, pos, depth, deepest)?;
+    // every operator moves the operands before it one level down
+    let mut depth = depth;
+    while let Some(token) = tokens.get(*pos) {
+        let (prec, assoc) = match (token, precedence(token)) {
+            (Token::BinaryOperator(_) | Token::UnaryOperatorRight(_), Some(prec)) => prec,
+            _ => break,
         };
-        tokens.drain(from..=to);
-        tokens.insert(from, Token::Number(num));
-        return Ok(true);
-    } else if tokens.len() != 1 {
-        Err(SolveErr::ExprWithNoResult(tokens.clone()))?;
-    }
-    Ok(false)
-}
-
-fn next_operation(tokens: &[Token]) -> Option<usize> {
-    let mut op_index = None::<usize>;
-    let mut op_priority = usize::MAX;
-    for (index, token) in tokens.iter().enumerate() {
-        if token.priority() < op_priority {
-            let before1 = tokens.get(index.saturating_sub(1)).map(TokenType::from);
-            let before1 = if index == 0 { None } else { before1 };
-            let current = tokens.get(index).map(TokenType::from);
-            let after1 = tokens.get(index + 1).map(TokenType::from);
-            let after2 = tokens.get(index + 2).map(TokenType::from);
-            match (before1, current, after1, after2) {
-                (Some(NUM), Some(BIN), Some(NUM), _)
-                | (_, Some(STA), Some(NUM), Some(END))
-                | (_, Some(UNL), Some(NUM), _)
-                | (Some(NUM), Some(UNR), _, _) => {
-                    op_index = Some(index);
-                    op_priority = token.priority();
-                }
-                _ => (),
-            }
-        }
-        if op_priority == 0 {
+        if prec < min_prec {
             break;
         }
+        depth += 1;
+        *deepest = depth.max(*deepest);
+        if depth > ast::MAX_NESTING {
+            return None;
+        }
+        *pos += 1;
+        lhs = match token {
+            Token::BinaryOperator(op) => {
+                let next_prec = match assoc {
+                    Assoc::Left => prec + 1,
+                    Assoc::Right => prec,
+                };
+                let rhs = parse_expr(tokens, pos, next_prec, depth, deepest)?;
+                Expr::Binary(op.clone(), Box::new(lhs), Box::new(rhs))
+            }
+            Token::UnaryOperatorRight(op) => Expr::UnaryRight(op.clone(), Box::new(lhs)),
+            _ => unreachable!(),
+        };
     }
-    op_index
+    Some(lhs)
 }
 
-fn calculate(nums: &[&BigFraction], op: &BinaryOp) -> Result<BigFraction, Error> {
-    match op {
-        BinaryOp::Mod | BinaryOp::Div => {
-            if nums[1].is_zero() {
-                let vec = vec![
-                    Token::from(nums[0].clone()),
-                    Token::from(op.clone()),
-                    Token::from(nums[1].clone()),
-                ];
-                Err(SolveErr::OperIllegalValues(vec))?;
+/// parses a number, a variable, a block or a left unary operator with its operand
+fn parse_operand(
+    tokens: &[Token],
+    pos: &mut usize,
+    depth: usize,
+    deepest: &mut usize,
+) -> Option<Expr> {
+    *deepest = depth.max(*deepest);
+    if depth > ast::MAX_NESTING {
+        return None;
+    }
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+    let depth = depth + 1;
+    let expr = match token {
+        Token::Number(num) => Expr::Number(num.clone()),
+        Token::Identifier(name) => Expr::Variable(name.clone()),
+        Token::UnaryOperatorLeft(op) => {
+            let (prec, _) = precedence(token)?;
+            Expr::UnaryLeft(
+                op.clone(),
+                Box::new(parse_expr(tokens, pos, prec, depth, deepest)?),
+            )
+        }
+        Token::StartBlock(StartBlock::Call(name)) => {
+            let mut args = Vec::new();
+            if tokens.get(*pos) == Some(&Token::from(EndBlock::Bracket)) {
+                *pos += 1;
+                return Some(Expr::Call(name.clone(), args));
             }
+            loop {
+                args.push(parse_expr(tokens, pos, 0, depth, deepest)?);
+                *pos += 1;
+                match tokens.get(*pos - 1)? {
+                    Token::Separator => continue,
+                    Token::EndBlock(EndBlock::Bracket) => break,
+                    _ => return None,
+                }
+            }
+            Expr::Call(name.clone(), args)
         }
-        _ => unreachable!(),
-    }
-    match op {
-        BinaryOp::Mod => Ok(nums[0] % nums[1]),
-        BinaryOp::Div => Ok(nums[0] / nums[1]),
-        _ => unreachable!(),
-    }
+        Token::StartBlock(start) => {
+            let expr = parse_expr(tokens, pos, 0, depth, deepest)?;
+            *pos += 1;
+            if tokens.get(*pos - 1)? != &Token::from(start.corrisp()) {
+                return None;
+            }
+            Expr::Block(start.clone(), Box::new(expr))
+        }
+        _ => return None,
+    };
+    Some(expr)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use fraction::{BigFraction, BigUint, Zero};
 
     #[test]
     fn test_parsing() -> Result<(), Error> {
@@ -408,8 +868,8 @@ mod tests {
     fn test_fix() -> Result<(), Error> {
         let rule1 = &[FixRules::BlockProduct];
         let rule2 = &[FixRules::CloseBlocks];
-        let mut actual_tokens_rule1 = parse_tokens("()(())||")?;
-        fix_tokens(&mut actual_tokens_rule1, rule1);
+        let (mut actual_tokens_rule1, mut spans1) = tokenize("()(())||", 0)?;
+        fix_tokens(&mut actual_tokens_rule1, &mut spans1, rule1);
         let expected_tokens_rule1 = vec![
             Token::from(StartBlock::Bracket),
             Token::from(EndBlock::Bracket),
@@ -422,8 +882,8 @@ mod tests {
             Token::from(StartBlock::Abs),
             Token::from(EndBlock::Abs),
         ];
-        let mut actual_tokens_rule2 = parse_tokens("(|(")?;
-        fix_tokens(&mut actual_tokens_rule2, rule2);
+        let (mut actual_tokens_rule2, mut spans2) = tokenize("(|(", 0)?;
+        fix_tokens(&mut actual_tokens_rule2, &mut spans2, rule2);
         let expected_tokens_rule2 = vec![
             Token::from(StartBlock::Bracket),
             Token::from(StartBlock::Abs),
@@ -436,6 +896,8 @@ mod tests {
         assert_eq!(actual_tokens_rule2, expected_tokens_rule2);
         assert_ne!(actual_tokens_rule1, expected_tokens_rule2);
         assert_ne!(actual_tokens_rule2, expected_tokens_rule1);
+        assert_eq!(spans1[2], Span::new(2, 2));
+        assert_eq!(spans2[3..], [Span::new(3, 3); 3]);
         Ok(())
     }
 
@@ -443,11 +905,11 @@ mod tests {
     fn test_check() -> Result<(), Error> {
         let rule1 = &[CheckRules::DenyMultipleSign];
         let rule2 = &[CheckRules::DenyAllMultipleSign];
-        let valid1 = check_rules(&parse_tokens("-+3.8*(1+|7|*-(5+|-1|))")?, &[]);
-        let test_rule1_ok = check_rules(&parse_tokens("-3++5+-1-+4--2")?, rule1);
-        let test_rule1_err = check_rules(&parse_tokens("--5")?, rule1);
-        let test_rule2_ok = check_rules(&parse_tokens("-3+5")?, rule2);
-        let test_rule2_err = check_rules(&parse_tokens("4--5")?, rule2);
+        let valid1 = check_rules(&parse_tokens("-+3.8*(1+|7|*-(5+|-1|))")?, &[], &[]);
+        let test_rule1_ok = check_rules(&parse_tokens("-3++5+-1-+4--2")?, &[], rule1);
+        let test_rule1_err = check_rules(&parse_tokens("--5")?, &[], rule1);
+        let test_rule2_ok = check_rules(&parse_tokens("-3+5")?, &[], rule2);
+        let test_rule2_err = check_rules(&parse_tokens("4--5")?, &[], rule2);
         assert!(valid1.is_ok());
         assert!(test_rule1_ok.is_ok());
         assert!(test_rule1_err.is_err());
@@ -457,13 +919,649 @@ mod tests {
     }
 
     #[test]
-    fn test_next_op() -> Result<(), Error> {
+    fn test_denied() {
+        let num = || Token::parse_num("2").unwrap();
+        // operators which can't fail, so they have no deny rule
+        let allowed = [ADD, SUB, MUL, POS, NEG];
+        for (op, ..) in PRECEDENCE {
+            let rules = DENIED.iter().filter(|(_, denied)| denied == &op);
+            assert_eq!(rules.count() + allowed.contains(&op) as usize, 1, "{op:?}");
+        }
+        let deny_op = DENIED.map(|(rule, _)| rule);
+        assert_eq!(deny_op, CheckRules::DENY_OP);
+        for (rule, op) in DENIED {
+            let tokens = match op {
+                Token::BinaryOperator(_) => vec![num(), op, num()],
+                Token::UnaryOperatorLeft(_) => vec![op, num()],
+                _ => vec![num(), op],
+            };
+            let broken = Error::from(CheckErr::BrokenCheckRule(rule));
+            assert_eq!(check_rules(&tokens, &[], &[rule]), Err(vec![broken]));
+            assert_eq!(check_rules(&tokens, &[], &CheckRules::DENY_SIGN), Ok(()));
+            let others = deny_op.iter().filter(|other| *other != &rule).copied();
+            assert_eq!(
+                check_rules(&tokens, &[], &others.collect::<Vec<_>>()),
+                Ok(())
+            );
+        }
+        let actual = resolve("!5", &mut Env::default(), &[], &[DENY_DERANG], None, false);
+        assert_eq!(
+            actual.map_err(Error::unspanned),
+            Err(Error::from(CheckErr::BrokenCheckRule(DENY_DERANG)))
+        );
+    }
+
+    #[test]
+    fn test_implicit_product() -> Result<(), Error> {
+        let mut env = Env::default();
+        let mut solve = |str: &str, fixes: &[FixRules], checks: &[CheckRules]| {
+            resolve(str, &mut env, fixes, checks, Some(10), false).map_err(Error::unspanned)
+        };
+        let implicit = &[FixRules::ImplicitProduct];
+        let num = |n: i64| Value::exact(BigFraction::from(n));
+        assert_eq!(solve("2(3+4)", implicit, &[])?, num(14));
+        assert_eq!(solve("(1+2)3", implicit, &[])?, num(9));
+        assert_eq!(solve("3|-2|", implicit, &[])?, num(6));
+        assert_eq!(solve("3!2", implicit, &[])?, num(12));
+        assert_eq!(solve("2sqrt(9)", implicit, &[])?, num(6));
+        assert_eq!(solve("x = 4", implicit, &[])?, num(4));
+        assert_eq!(solve("3x", implicit, &[])?, num(12));
+        assert_eq!(solve("-2(3)", implicit, &[])?, num(-6));
+        assert_eq!(
+            solve("0.5(3)", implicit, &[])?.num,
+            BigFraction::new(8u8, 15u8)
+        );
+        let incomplete = |num: &str| Err(Error::from(ParseErr::IncompleteRepetend(num.into())));
+        assert_eq!(solve("0.(3", &FixRules::ALL, &[]), incomplete("0.("));
+        let half = |n: u8| Value::exact(BigFraction::new(n, 2u8));
+        assert_eq!(solve("0.5 (3)", implicit, &[])?, half(3));
+        assert_eq!(solve("0.5\t(2)", implicit, &[])?, num(1));
+        assert_eq!(solve("2.5(1+2)", implicit, &[])?, half(15));
+        assert_eq!(solve("2.5 (1+2)", implicit, &[])?, half(15));
+        assert_eq!(solve("2 (3)", implicit, &[])?, num(6));
+        let two_pi = solve("2pi", implicit, &[])?;
+        assert_eq!(two_pi, solve("tau", implicit, &[])?);
+        assert!(solve("1 2", &FixRules::ALL, &[]).is_err());
+        assert!(solve("(1)(2)", implicit, &[]).is_err());
+        assert!(solve("2(3)", &[FixRules::BlockProduct], &[]).is_err());
+        let broken = Err(Error::from(CheckErr::BrokenCheckRule(DENY_IMP)));
+        assert_eq!(solve("2(3)", implicit, &[DENY_IMP]), broken);
+        assert_eq!(solve("2(3)", &[], &[DENY_IMP]), broken);
+        assert_eq!(solve("(2)(3)", &FixRules::ALL, &[DENY_IMP]), broken);
+        assert_eq!(solve("2*(3)", &FixRules::ALL, &[DENY_IMP])?, num(6));
+        let span = |str: &str| {
+            let res = resolve(str, &mut Env::default(), implicit, &[DENY_IMP], None, false);
+            res.err().and_then(|err| err.span())
+        };
+        assert_eq!(span("1 + 2 (3)"), Some(Span::new(4, 7)));
+        assert_eq!(span("1 + 2pi"), Some(Span::new(4, 7)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree() -> Result<(), Error> {
+        let num = |n: i64| Box::new(Expr::Number(BigFraction::from(n)));
         let expr1 = parse("12+34*45", &FixRules::ALL, &[])?;
         let expr2 = parse("12+(12)", &FixRules::ALL, &[])?;
         let expr3 = parse("12+(12/34)", &FixRules::ALL, &[])?;
-        assert_eq!(next_operation(&expr1), Some(3));
-        assert_eq!(next_operation(&expr2), Some(2));
-        assert_eq!(next_operation(&expr3), Some(4));
+        let expected1 = Expr::Binary(
+            BinaryOp::Add,
+            num(12),
+            Box::new(Expr::Binary(BinaryOp::Mul, num(34), num(45))),
+        );
+        let expected2 = Expr::Binary(
+            BinaryOp::Add,
+            num(12),
+            Box::new(Expr::Block(StartBlock::Bracket, num(12))),
+        );
+        let expected3 = Expr::Binary(
+            BinaryOp::Add,
+            num(12),
+            Box::new(Expr::Block(
+                StartBlock::Bracket,
+                Box::new(Expr::Binary(BinaryOp::Div, num(12), num(34))),
+            )),
+        );
+        assert_eq!(expr1, expected1);
+        assert_eq!(expr2, expected2);
+        assert_eq!(expr3, expected3);
+        assert_eq!(expr3.to_string(), "12 + ( 12 / 34 )");
+        assert!(parse("1 2", &FixRules::ALL, &[]).is_err());
+        assert!(parse("1 +", &FixRules::ALL, &[]).is_err());
+        assert!(parse("()", &FixRules::ALL, &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifiers() -> Result<(), Error> {
+        let actual_res1 = parse_tokens("x_1+-y2!")?;
+        let expected_res1 = vec![
+            Token::Identifier(String::from("x_1")),
+            Token::from(BinaryOp::Add),
+            Token::from(UnaryOpLeft::Neg),
+            Token::Identifier(String::from("y2")),
+            Token::from(UnaryOpRight::Fact),
+        ];
+        assert_eq!(actual_res1, expected_res1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_assignment() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve("x = 3/7", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual2 = resolve("x * 7 + y", &mut env, &FixRules::ALL, &[], None, false);
+        let actual3 = resolve("y = x * 7", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual4 = resolve("2 = x", &mut env, &FixRules::ALL, &[], None, false);
+        assert_eq!(actual1, Value::from(BigFraction::new(3u8, 7u8)));
+        assert_eq!(
+            actual2.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::UnknownVariable("y".into())))
+        );
+        assert_eq!(actual3, Value::from(BigFraction::from(3)));
+        assert_eq!(env.get("y"), Some(&Value::from(BigFraction::from(3))));
+        assert!(actual4.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<(), Error> {
+        let mut env = Env::default();
+        resolve("1/3", &mut env, &FixRules::ALL, &[], None, false)?;
+        resolve("x = 2", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual1 = resolve("ans * $1 + $2", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual2 = resolve("$4", &mut env, &FixRules::ALL, &[], None, false);
+        let actual3 = resolve("ans = 3", &mut env, &FixRules::ALL, &[], None, false);
+        assert_eq!(actual1, Value::from(BigFraction::new(8u8, 3u8)));
+        assert_eq!(
+            actual2.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::UnknownVariable("$4".into())))
+        );
+        assert!(actual3.is_err());
+        assert_eq!(env.history().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_call() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual_res1 = parse_tokens("gcd(12, -x)")?;
+        let expected_res1 = vec![
+            Token::from(StartBlock::Call(String::from("gcd"))),
+            Token::parse_num("12")?,
+            Token::Separator,
+            Token::from(UnaryOpLeft::Neg),
+            Token::Identifier(String::from("x")),
+            Token::from(EndBlock::Bracket),
+        ];
+        let actual2 = resolve(
+            "max(1, 2*3, (4)) + abs(-1/2)",
+            &mut env,
+            &[],
+            &[],
+            None,
+            false,
+        )?;
+        let actual3 = resolve("floor(7/2", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual4 = resolve("sign(1, 2)", &mut env, &[], &[], None, false);
+        assert_eq!(actual_res1, expected_res1);
+        assert_eq!(actual2, Value::from(BigFraction::new(13u8, 2u8)));
+        assert_eq!(actual3, Value::from(BigFraction::from(3)));
+        assert!(matches!(
+            actual4.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::WrongArity(..)))
+        ));
+        assert!(parse_tokens("1, 2").is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_define() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = define("f(x, y) = x^2 + y/3", &mut env, &FixRules::ALL, &[])?;
+        let actual2 = resolve(
+            "f(2, 1) * f(0, 3)",
+            &mut env,
+            &FixRules::ALL,
+            &[],
+            None,
+            false,
+        )?;
+        let actual3 = define("g(x) = x + z", &mut env, &FixRules::ALL, &[]);
+        let actual4 = define("g(x) = h(x)", &mut env, &FixRules::ALL, &[]);
+        define("g(x) = g(x) + 1", &mut env, &FixRules::ALL, &[])?;
+        let actual5 = resolve("g(1)", &mut env, &FixRules::ALL, &[], None, false);
+        let actual6 = resolve("f(1)", &mut env, &FixRules::ALL, &[], None, false);
+        let actual7 = define("x = 3", &mut env, &FixRules::ALL, &[])?;
+        let actual8 = define("f(x, x) = x", &mut env, &FixRules::ALL, &[]);
+        assert_eq!(actual1, Some(String::from("f")));
+        assert_eq!(actual2, Value::from(BigFraction::new(13u8, 3u8)));
+        assert_eq!(
+            actual3.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::UnknownVariable("z".into())))
+        );
+        assert_eq!(
+            actual4.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::UnknownFunction("h".into())))
+        );
+        assert_eq!(
+            actual5.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::RecursionLimit("g".into())))
+        );
+        assert!(matches!(
+            actual6.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::WrongArity(..)))
+        ));
+        assert_eq!(actual7, None);
+        assert!(actual8.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_precedence() -> Result<(), Error> {
+        let mut env = Env::default();
+        let table = [
+            ("2 + 3 + 4", "(2 + 3) + 4"),
+            ("2 + 3 - 4", "(2 + 3) - 4"),
+            ("2 + 3 * 4", "2 + (3 * 4)"),
+            ("2 + 3 / 4", "2 + (3 / 4)"),
+            ("2 + 3 % 4", "2 + (3 % 4)"),
+            ("2 + 3 ^ 4", "2 + (3 ^ 4)"),
+            ("2 - 3 + 4", "(2 - 3) + 4"),
+            ("2 - 3 - 4", "(2 - 3) - 4"),
+            ("2 - 3 * 4", "2 - (3 * 4)"),
+            ("2 - 3 / 4", "2 - (3 / 4)"),
+            ("2 - 3 % 4", "2 - (3 % 4)"),
+            ("2 - 3 ^ 4", "2 - (3 ^ 4)"),
+            ("2 * 3 + 4", "(2 * 3) + 4"),
+            ("2 * 3 - 4", "(2 * 3) - 4"),
+            ("2 * 3 * 4", "(2 * 3) * 4"),
+            ("2 * 3 / 4", "(2 * 3) / 4"),
+            ("2 * 3 % 4", "(2 * 3) % 4"),
+            ("2 * 3 ^ 4", "2 * (3 ^ 4)"),
+            ("2 / 3 + 4", "(2 / 3) + 4"),
+            ("2 / 3 - 4", "(2 / 3) - 4"),
+            ("2 / 3 * 4", "(2 / 3) * 4"),
+            ("2 / 3 / 4", "(2 / 3) / 4"),
+            ("2 / 3 % 4", "(2 / 3) % 4"),
+            ("2 / 3 ^ 4", "2 / (3 ^ 4)"),
+            ("2 % 3 + 4", "(2 % 3) + 4"),
+            ("2 % 3 - 4", "(2 % 3) - 4"),
+            ("2 % 3 * 4", "(2 % 3) * 4"),
+            ("2 % 3 / 4", "(2 % 3) / 4"),
+            ("2 % 3 % 4", "(2 % 3) % 4"),
+            ("2 % 3 ^ 4", "2 % (3 ^ 4)"),
+            ("2 ^ 3 + 4", "(2 ^ 3) + 4"),
+            ("2 ^ 3 - 4", "(2 ^ 3) - 4"),
+            ("2 ^ 3 * 4", "(2 ^ 3) * 4"),
+            ("2 ^ 3 / 4", "(2 ^ 3) / 4"),
+            ("2 ^ 3 % 4", "(2 ^ 3) % 4"),
+            ("2 ^ 3 ^ 4", "2 ^ (3 ^ 4)"),
+            ("-2 ^ 2", "-(2 ^ 2)"),
+            ("2 ^ -2", "2 ^ (-2)"),
+            ("-2 * 3", "(-2) * 3"),
+            ("-2 % 3", "(-2) % 3"),
+            ("2 * -3 ^ 2", "2 * (-(3 ^ 2))"),
+            ("--2 ^ 2", "-(-(2 ^ 2))"),
+            ("-3!", "-(3!)"),
+            ("3! ^ 2", "(3!) ^ 2"),
+            ("2 ^ 3!", "2 ^ (3!)"),
+            ("!3 ^ 2", "!(3 ^ 2)"),
+            ("!3!", "!(3!)"),
+            ("!3 * 2", "(!3) * 2"),
+        ];
+        for (expr, grouped) in table {
+            let actual = resolve(expr, &mut env, &FixRules::ALL, &[], None, false)?;
+            let expected = resolve(grouped, &mut env, &FixRules::ALL, &[], None, false)?;
+            assert_eq!(actual, expected, "'{expr}' should be solved as '{grouped}'");
+        }
+        let actual1 = resolve("2^3^2", &mut env, &FixRules::ALL, &[], None, false)?;
+        let actual2 = resolve("-2^2", &mut env, &FixRules::ALL, &[], None, false)?;
+        assert_eq!(actual1, Value::from(BigFraction::from(512)));
+        assert_eq!(actual2, Value::from(BigFraction::from(-4)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explanation() -> Result<(), Vec<Error>> {
+        let steps = |str: &str| {
+            let mut steps = vec![];
+            resolve_steps(
+                str,
+                &mut Env::default(),
+                &FixRules::ALL,
+                &[],
+                None,
+                &mut steps,
+            )?;
+            Ok::<_, Vec<Error>>(steps)
+        };
+        let expected = ["( - 2 ) ^ 2", "( -2 ) ^ 2", "4"];
+        assert_eq!(steps("(-2)^2")?, expected);
+        let mut rounded = vec![];
+        let env = &mut Env::default();
+        resolve_steps("sqrt(2) + 1", env, &[], &[], Some(5), &mut rounded)?;
+        assert_eq!(rounded, ["sqrt( 2 ) + 1", "≈1.41421 + 1", "≈2.41421"]);
+        // each step, solved again, has the same solution
+        for str in [
+            "(-2)^2",
+            "(1/2)^2",
+            "2^(1/2)^2",
+            "6/(1/2)",
+            "6%(1/2)",
+            "(2-4)^3",
+            "(3/2)! * 1",
+        ] {
+            let res = resolve_all(
+                str,
+                &mut Env::default(),
+                &FixRules::ALL,
+                &[],
+                Some(5),
+                false,
+            );
+            for step in steps(str).unwrap_or_default() {
+                let step_res = resolve_all(
+                    &step,
+                    &mut Env::default(),
+                    &FixRules::ALL,
+                    &[],
+                    Some(5),
+                    false,
+                );
+                assert_eq!(
+                    step_res.ok(),
+                    res.clone().ok(),
+                    "'{step}' explaining '{str}'"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve("sqrt(2)", &mut env, &FixRules::ALL, &[], Some(10), false)?;
+        let actual2 = resolve("sqrt(2)", &mut env, &FixRules::ALL, &[], None, false);
+        let actual3 = resolve("2 * pi", &mut env, &FixRules::ALL, &[], Some(5), false)?;
+        let actual4 = resolve(
+            "2^(1/3) * 2^(2/3)",
+            &mut env,
+            &FixRules::ALL,
+            &[],
+            Some(5),
+            false,
+        )?;
+        let actual5 = resolve(
+            "4^(1/2) + ln(1)",
+            &mut env,
+            &FixRules::ALL,
+            &[],
+            Some(5),
+            false,
+        )?;
+        let actual6 = resolve("x = e", &mut env, &FixRules::ALL, &[], Some(5), false)?;
+        let actual7 = resolve("x * 0 + 1", &mut env, &FixRules::ALL, &[], Some(5), false)?;
+        let expected1 = BigFraction::new(14142135624u64, 10000000000u64);
+        assert_eq!(actual1, Value::approx(expected1));
+        assert!(matches!(
+            actual2.clone().map_err(Error::unspanned),
+            Err(Error::Solve(SolveErr::IrrationalResult(..)))
+        ));
+        assert_eq!(
+            actual3,
+            Value::approx(BigFraction::new(628319u32, 100000u32))
+        );
+        assert_eq!(actual4, Value::approx(BigFraction::from(2)));
+        assert_eq!(actual5, Value::exact(BigFraction::from(2)));
+        assert_eq!(
+            actual6,
+            Value::approx(BigFraction::new(271828u32, 100000u32))
+        );
+        assert!(!actual7.exact);
+        Ok(())
+    }
+
+    #[test]
+    fn test_constants() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve(
+            "tau - 2 * pi",
+            &mut env,
+            &FixRules::ALL,
+            &[],
+            Some(20),
+            false,
+        )?;
+        let actual2 = resolve(
+            "phi^2 - phi",
+            &mut env,
+            &FixRules::ALL,
+            &[],
+            Some(20),
+            false,
+        )?;
+        let actual3 = resolve("pi = 3", &mut env, &FixRules::ALL, &[], Some(20), false);
+        let actual4 = define("f(x) = e^x", &mut env, &FixRules::ALL, &[])?;
+        let actual5 = define("g(e) = e", &mut env, &FixRules::ALL, &[]);
+        assert_eq!(actual1, Value::approx(BigFraction::zero()));
+        assert_eq!(actual2, Value::approx(BigFraction::from(1)));
+        assert!(actual3.is_err());
+        assert_eq!(actual4, Some(String::from("f")));
+        assert!(actual5.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_radix() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve("0x1F + 0b1011 - 0o755", &mut env, &[], &[], None, false)?;
+        let actual2 = resolve(
+            "0xFFFF_FFFF_FFFF_FFFF_FFFF",
+            &mut env,
+            &[],
+            &[],
+            None,
+            false,
+        )?;
+        let actual3 = parse_tokens("0b102");
+        let actual4 = parse_tokens("0x1G + 1");
+        let actual5 = parse_tokens("0x");
+        let expected2 = BigFraction::from(BigUint::from(2u8).pow(80u32) - 1u8);
+        assert_eq!(actual1, Value::from(BigFraction::from(31 + 11 - 493)));
+        assert_eq!(actual2, Value::from(expected2));
+        assert_eq!(
+            actual3.clone().map_err(Error::unspanned),
+            Err(Error::Parse(ParseErr::InvalidDigit("0b102".into(), 4, 2)))
+        );
+        assert_eq!(
+            actual4.clone().map_err(Error::unspanned),
+            Err(Error::Parse(ParseErr::InvalidDigit("0x1G".into(), 3, 16)))
+        );
+        assert_eq!(
+            actual5.clone().map_err(Error::unspanned),
+            Err(Error::Parse(ParseErr::InvalidNumber("0x".into())))
+        );
+        assert_eq!(
+            actual3.unwrap_err().to_string(),
+            "ParseErr: invalid digit '2' in base 2 number '0b102' at position 5"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_exponent_notation() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve("6.022e23", &mut env, &[], &[], None, false)?;
+        let actual2 = resolve("1.6E-19 * 1e+19", &mut env, &[], &[], None, false)?;
+        let actual3 = parse_tokens("2e")?;
+        let actual4 = parse_tokens("1e99999");
+        let expected1 =
+            BigFraction::from(6022u32) * BigFraction::from(BigUint::from(10u8).pow(20u32));
+        assert_eq!(actual1, Value::from(expected1));
+        assert_eq!(actual2, Value::from(BigFraction::new(16u8, 10u8)));
+        assert_eq!(
+            actual3,
+            vec![Token::parse_num("2")?, Token::Identifier(String::from("e"))]
+        );
+        assert_eq!(
+            actual4.clone().map_err(Error::unspanned),
+            Err(Error::Parse(ParseErr::InvalidNumber("1e99999".into())))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeating() -> Result<(), Error> {
+        let mut env = Env::default();
+        let actual1 = resolve("0.(3)", &mut env, &[], &[], None, false)?;
+        let actual2 = resolve("1.1(6) + 0.(142_857)", &mut env, &[], &[], None, false)?;
+        let actual3 = parse_tokens("2.(3+1)")?;
+        let actual4 = parse_tokens("0.(3)(3)")?;
+        assert_eq!(actual1, Value::from(BigFraction::new(1u8, 3u8)));
+        assert_eq!(actual2, Value::from(BigFraction::new(55u8, 42u8)));
+        assert_eq!(
+            actual3[..2],
+            [Token::parse_num("2")?, Token::from(StartBlock::Bracket)]
+        );
+        assert_eq!(
+            parse_tokens("1.2(34").map_err(Error::unspanned),
+            Err(Error::from(ParseErr::IncompleteRepetend("1.2(".into())))
+        );
+        assert_eq!(actual4.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut env = Env::default();
+        let span = |str: &str, explain: bool| {
+            let res = resolve(str, &mut Env::default(), &FixRules::ALL, &[], None, explain);
+            res.err().and_then(|err| err.span())
+        };
+        assert_eq!(span("1 + 5 / 0", false), Some(Span::new(4, 9)));
+        assert_eq!(span("(1 + 2) / (3 - 3)", true), Some(Span::new(0, 17)));
+        assert_eq!(span("x = 2 * y", false), Some(Span::new(8, 9)));
+        assert_eq!(span("2 + 0x1G", false), Some(Span::new(4, 8)));
+        assert_eq!(span("2 # 3", false), Some(Span::new(2, 3)));
+        assert_eq!(span("sqrt(2) + 1", false), Some(Span::new(0, 7)));
+        assert_eq!(span("1 + 2)", false), Some(Span::new(5, 6)));
+        let define = define("f(x) = x + z", &mut env, &FixRules::ALL, &[]);
+        assert_eq!(
+            define.err().and_then(|err| err.span()),
+            Some(Span::new(11, 12))
+        );
+        let err = resolve("1 + 5 / 0", &mut env, &FixRules::ALL, &[], None, false);
+        let underline = err.err().and_then(|err| err.underline("1 + 5 / 0"));
+        assert_eq!(underline, Some(String::from("    ^^^^^")));
+    }
+
+    #[test]
+    fn test_all_errors() {
+        let mut env = Env::default();
+        let checks = &[DENY_DIV, DENY_EXP];
+        let line = "1 # 2 / 3 ^ 0x1G) + (4";
+        let errs = resolve_all(line, &mut env, &[], checks, None, false).unwrap_err();
+        let spans = errs.iter().map(|err| err.span()).collect::<Vec<_>>();
+        let expected = [(2, 3), (12, 16), (16, 17), (6, 7), (10, 11), (20, 22)];
+        let expected = expected.map(|(start, end)| Some(Span::new(start, end)));
+        assert_eq!(spans, expected);
+        assert_eq!(
+            errs[0].clone().unspanned(),
+            Error::from(ParseErr::InvalidToken(String::from("#")))
+        );
+        assert_eq!(
+            resolve(line, &mut env, &[], checks, None, false),
+            Err(errs[0].clone())
+        );
+        assert_eq!(parse_all("2 / 3 / 4", &[], checks).unwrap_err().len(), 2);
+        let errs = define_all("f(x) = y + x * z", &mut env, &[], &[]).unwrap_err();
+        assert_eq!(
+            errs.into_iter().map(Error::unspanned).collect::<Vec<_>>(),
+            vec![
+                Error::from(SolveErr::UnknownVariable(String::from("y"))),
+                Error::from(SolveErr::UnknownVariable(String::from("z"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nesting() {
+        // as deep as the stack of the main thread, where the shell solves expressions
+        let nesting = || {
+            let max = ast::MAX_NESTING;
+            let nested = |open: &str, close: &str, n: usize| {
+                format!("{}1{}", open.repeat(n), close.repeat(n))
+            };
+            let mut env = Env::default();
+            let body = nested("(", ")", max - 2).replace('1', "f(x)");
+            define(&format!("f(x) = {body}"), &mut env, &[], &[]).unwrap();
+            define("g(x) = |x|", &mut env, &[], &[]).unwrap();
+            let mut solve = |str: &str| {
+                let res = resolve(str, &mut env, &FixRules::ALL, &[], None, false);
+                res.map_err(Error::unspanned)
+            };
+            let too_nested = Err(Error::from(ParseErr::TooNested(max)));
+            assert!(solve(&nested("(", ")", max)).is_ok());
+            assert!(solve(&nested("g(", ")", max / 2)).is_ok());
+            assert_eq!(solve(&nested("(", ")", max + 1)), too_nested);
+            assert_eq!(solve(&nested("-", "", max + 1)), too_nested);
+            assert_eq!(solve(&nested("1+", "", max + 1)), too_nested);
+            assert_eq!(solve(&nested("2^", "", 100_000)), too_nested);
+            assert_eq!(solve(&nested("(", "", 100_000)), too_nested);
+            assert_eq!(
+                solve("f(1)"),
+                Err(Error::from(SolveErr::RecursionLimit(String::from("f"))))
+            );
+        };
+        let thread = std::thread::Builder::new().stack_size(8 << 20);
+        thread.spawn(nesting).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_rule_names() {
+        for rule in CheckRules::ALL {
+            assert_eq!(CheckRules::from_name(&rule.to_string()), Some(rule));
+        }
+        for rule in FixRules::ALL {
+            assert_eq!(FixRules::from_name(&rule.to_string()), Some(rule));
+        }
+        assert_eq!(CheckRules::from_name("deny-division"), Some(DENY_DIV));
+        assert_eq!(
+            FixRules::from_name("block-product"),
+            Some(FixRules::BlockProduct)
+        );
+        assert_eq!(CheckRules::from_name("DenyDivision"), None);
+    }
+
+    /// pieces of the lines tried by test_no_panics, random characters rarely form names
+    const PIECES: [&str; 32] = [
+        "0", "2", "1.5", "0x1f", "0b2", "6e2", "0.(3)", "1_0", "x", "pi", "e", "ans", "$1", "$",
+        "sqrt(", "ln(", "gcd(", "f(", "(", ")", "|", ",", "=", " ", "+", "-", "*", "/", "%", "!",
+        "^", "#",
+    ];
+
+    proptest::proptest! {
+        #[test]
+        fn test_no_panics(pieces in proptest::collection::vec(proptest::sample::select(&PIECES[..]), 0..16)) {
+            // only the first factorial or power is kept, as big ones take too long to solve
+            let first = pieces.iter().position(|piece| ["!", "^"].contains(piece));
+            let line = pieces
+                .iter()
+                .enumerate()
+                .filter(|(i, piece)| !["!", "^"].contains(piece) || Some(*i) == first)
+                .map(|(_, piece)| *piece)
+                .collect::<String>();
+            let mut env = Env::default();
+            define("f(x) = x * 2", &mut env, &[], &[]).unwrap();
+            for checks in [&[][..], &CheckRules::ALL] {
+                for fixes in [&[][..], &FixRules::ALL] {
+                    let _ = define(&line, &mut env.clone(), fixes, checks);
+                    let _ = resolve(&line, &mut env, fixes, checks, None, false);
+                    let _ = resolve(&line, &mut env, fixes, checks, Some(10), true);
+                }
+            }
+        }
+    }
 }
@@ -1,9 +1,13 @@
 #![allow(dead_code, unused)]
 
 use super::error::{Error, ParseErr};
+use crate::common::algs;
 use fraction::{BigFraction, BigUint, GenericFraction};
 use std::{fmt::Display, mem, str::FromStr};
 
+/// biggest exponent accepted in "1.5e[exp]", bigger ones take too long to expand
+const MAX_EXP: u32 = 10_000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     StartBlock(StartBlock),
@@ -12,6 +16,17 @@ pub enum Token {
     UnaryOperatorRight(UnaryOpRight),
     BinaryOperator(BinaryOp),
     Number(BigFraction),
+    /// approximation of an irrational number
+    Approx(BigFraction),
+    Identifier(String),
+    Separator,
+}
+
+/// byte range of a token in the parsed line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,12 +37,16 @@ pub enum TokenType {
     UnaryOperatorRight,
     BinaryOperator,
     Number,
+    Identifier,
+    Separator,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StartBlock {
     Bracket,
     Abs,
+    /// "name(", closed by a bracket
+    Call(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -101,19 +120,21 @@ impl From<&Token> for TokenType {
             Token::EndBlock(_) => Self::EndBlock,
             Token::UnaryOperatorLeft(_) => Self::UnaryOperatorLeft,
             Token::BinaryOperator(_) => Self::BinaryOperator,
-            Token::Number(_) => Self::Number,
+            Token::Number(_) | Token::Approx(_) => Self::Number,
             Token::UnaryOperatorRight(_) => Self::UnaryOperatorRight,
+            Token::Identifier(_) => Self::Identifier,
+            Token::Separator => Self::Separator,
         }
     }
 }
 
 impl Display for StartBlock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = match self {
-            StartBlock::Abs => "|",
-            StartBlock::Bracket => "(",
-        };
-        write!(f, "{str}")
+        match self {
+            StartBlock::Abs => write!(f, "|"),
+            StartBlock::Bracket => write!(f, "("),
+            StartBlock::Call(name) => write!(f, "{name}("),
+        }
     }
 }
 
@@ -170,6 +191,12 @@ impl Display for Token {
             Token::UnaryOperatorRight(str) => str.to_string(),
             Token::BinaryOperator(str) => str.to_string(),
             Token::Number(str) => str.to_string(),
+            Token::Approx(num) => match algs::decimal_places(num) {
+                Some(prec) => format!("≈{num:.prec$}"),
+                None => format!("≈{num}"),
+            },
+            Token::Identifier(str) => str.to_string(),
+            Token::Separator => String::from(","),
         };
         write!(f, "{str}")
     }
@@ -181,42 +208,87 @@ impl Display for TokenType {
     }
 }
 
-impl PartialOrd for Token {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
     }
-}
 
-impl Ord for Token {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.priority().cmp(&other.priority())
+    /// range from the first span to the last one, if there are any
+    pub fn join(spans: &[Span]) -> Option<Span> {
+        Some(Self::new(spans.first()?.start, spans.last()?.end))
     }
 }
 
 impl Token {
     pub fn parse_num(str: &str) -> Result<Self, Error> {
-        let num = BigFraction::from_str(str).or(Err(ParseErr::InvalidNumber(str.to_string())))?;
-        Ok(Token::Number(num))
+        if let Some(radix) = Self::radix(str) {
+            return Self::parse_int(str, radix);
+        }
+        let err = || ParseErr::InvalidNumber(str.to_string());
+        if let Some((fixed, repetend)) = str.strip_suffix(')').and_then(|str| str.split_once('(')) {
+            return Ok(Self::parse_repeating(fixed, repetend).ok_or_else(err)?);
+        }
+        let (mantissa, exp) = match str.split_once(['e', 'E']) {
+            Some((mantissa, exp)) => (mantissa, exp.replace('_', "").parse::<i32>().ok()),
+            None => (str, Some(0)),
+        };
+        let exp = exp
+            .filter(|exp| exp.unsigned_abs() <= MAX_EXP)
+            .ok_or_else(err)?;
+        let num = BigFraction::from_str(mantissa).ok().ok_or_else(err)?;
+        let scale = BigFraction::from(BigUint::from(10u8).pow(exp.unsigned_abs()));
+        match exp < 0 {
+            true => Ok(Token::Number(num / scale)),
+            false => Ok(Token::Number(num * scale)),
+        }
     }
 
-    pub fn num(&self) -> Option<&BigFraction> {
-        match self {
-            Token::Number(num) => Some(num),
+    /// radix of integers written as "0x1F", "0b1011" or "0o755"
+    pub fn radix(str: &str) -> Option<u32> {
+        match str.get(..2)? {
+            "0x" | "0X" => Some(16),
+            "0b" | "0B" => Some(2),
+            "0o" | "0O" => Some(8),
             _ => None,
         }
     }
 
-    pub fn priority(&self) -> usize {
+    /// decimal number whose last fractional digits repeat forever: "0.1(6)" => "1/6"
+    fn parse_repeating(fixed: &str, repetend: &str) -> Option<Self> {
+        let fixed_len = fixed.split_once('.')?.1.replace('_', "").len();
+        let repetend = repetend.replace('_', "");
+        let pow10 = |exp: usize| BigFraction::from(BigUint::from(10u8).pow(exp as u32));
+        // 0.1(6) = 0.1 + 6 / (10^1 * (10^1 - 1))
+        let denom = pow10(fixed_len) * (pow10(repetend.len()) - BigFraction::from(1));
+        let fixed = BigFraction::from_str(fixed).ok()?;
+        let repetend = BigFraction::from_str(&repetend).ok()?;
+        Some(Token::Number(fixed + repetend / denom))
+    }
+
+    /// integer after the radix prefix, whose digits may be separated by '_'
+    fn parse_int(str: &str, radix: u32) -> Result<Self, Error> {
+        let mut num = BigUint::from(0u8);
+        let mut empty = true;
+        for (index, c) in str.chars().enumerate().skip(2) {
+            if c == '_' {
+                continue;
+            }
+            let digit = c
+                .to_digit(radix)
+                .ok_or_else(|| ParseErr::InvalidDigit(str.to_string(), index, radix))?;
+            num = num * radix + digit;
+            empty = false;
+        }
+        if empty {
+            Err(ParseErr::InvalidNumber(str.to_string()))?;
+        }
+        Ok(Token::Number(BigFraction::from(num)))
+    }
+
+    pub fn num(&self) -> Option<&BigFraction> {
         match self {
-            Token::StartBlock(_) => 0,
-            Token::UnaryOperatorRight(_) => 1,
-            Token::UnaryOperatorLeft(_) => 2,
-            Token::BinaryOperator(op) => match op {
-                BinaryOp::Exp => 3,
-                BinaryOp::Mul | BinaryOp::Mod | BinaryOp::Div => 4,
-                BinaryOp::Add | BinaryOp::Sub => 5,
-            },
-            _ => usize::MAX,
+            Token::Number(num) | Token::Approx(num) => Some(num),
+            _ => None,
         }
     }
 
@@ -238,13 +310,13 @@ impl TokenType {
 impl StartBlock {
     pub fn corrisp(&self) -> EndBlock {
         match self {
-            StartBlock::Bracket => EndBlock::Bracket,
+            StartBlock::Bracket | StartBlock::Call(_) => EndBlock::Bracket,
             StartBlock::Abs => EndBlock::Abs,
         }
     }
 
-    pub fn is_corrisp(&self, other: EndBlock) -> bool {
-        self.corrisp() == other
+    pub fn is_corrisp(&self, other: &EndBlock) -> bool {
+        &self.corrisp() == other
     }
 }
 
@@ -256,7 +328,7 @@ impl EndBlock {
         }
     }
 
-    pub fn is_corrisp(&self, other: StartBlock) -> bool {
-        self.corrisp() == other
+    pub fn is_corrisp(&self, other: &StartBlock) -> bool {
+        other.is_corrisp(self)
     }
 }
@@ -0,0 +1,35 @@
+use fraction::BigFraction;
+use std::fmt::Display;
+
+/// result of an expression, which may only approximate an irrational number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Value {
+    pub num: BigFraction,
+    /// false if num is an approximation of the actual result
+    pub exact: bool,
+}
+
+impl Value {
+    pub fn exact(num: BigFraction) -> Self {
+        Self { num, exact: true }
+    }
+
+    pub fn approx(num: BigFraction) -> Self {
+        Self { num, exact: false }
+    }
+}
+
+impl From<BigFraction> for Value {
+    fn from(value: BigFraction) -> Self {
+        Self::exact(value)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.exact {
+            true => write!(f, "{}", self.num),
+            false => write!(f, "≈ {}", self.num),
+        }
+    }
+}
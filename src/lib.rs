@@ -1,7 +1,11 @@
 pub mod common;
 pub mod expression {
+    pub mod ast;
+    pub mod env;
     pub mod error;
+    pub mod function;
     pub mod solver;
     pub mod token;
+    pub mod value;
 }
 pub mod shell;
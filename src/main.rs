@@ -1,5 +1,7 @@
 use calculator::shell;
+use std::{env, process::ExitCode};
 
-fn main() {
-    shell::run();
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    shell::cli::run(&args)
 }
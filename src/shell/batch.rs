@@ -0,0 +1,222 @@
+use std::{io, process::ExitCode};
+
+use super::{
+    eval, fmt_errors, help, json, show_funcs, show_history, show_steps, show_vars, solutions,
+    Options, Output, Solved,
+};
+use crate::expression::{env::Env, error::Error, value::Value};
+
+/// exit code of invalid arguments, unreadable files and failed commands,
+/// errors in the expressions have their own
+pub const USAGE_ERR: u8 = 1;
+
+/// evaluates lines as the shell does, without prompts and with each solution on one line
+pub struct Batch {
+    opt: Options,
+    env: Env,
+    /// evaluate the lines after a failed one
+    keep_going: bool,
+    /// exit code of the first failure, 0 if there are none
+    code: u8,
+}
+
+impl Batch {
+    pub fn new(opt: Options, keep_going: bool) -> Self {
+        Self {
+            opt,
+            env: Env::default(),
+            keep_going,
+            code: 0,
+        }
+    }
+
+    /// evaluates each line, whose errors tell its line number in source if there is one,
+    /// returns false if the lines after them mustn't be evaluated
+    pub fn eval_lines<I>(&mut self, lines: I, source: Option<&str>) -> bool
+    where
+        I: IntoIterator<Item = io::Result<String>>,
+    {
+        for (index, line) in lines.into_iter().enumerate() {
+            let title = match source {
+                Some(source) => format!("Error ({source}:{}):", index + 1),
+                None => String::from("Error:"),
+            };
+            let keep_going = match line {
+                // everything after "#" is a comment
+                Ok(line) => self.eval_line(line.split('#').next().unwrap_or_default(), &title),
+                Err(err) => {
+                    // the lines after an unreadable one can't be found
+                    eprintln!("{title} {err}");
+                    self.failed(USAGE_ERR);
+                    false
+                }
+            };
+            if !keep_going {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// evaluates an expression given as an argument, where "#" isn't a comment,
+    /// returns false if the ones after it mustn't be evaluated
+    pub fn eval_expr(&mut self, expr: &str) -> bool {
+        self.eval_line(expr, "Error:")
+    }
+
+    /// returns false if the lines after it mustn't be evaluated
+    fn eval_line(&mut self, line: &str, title: &str) -> bool {
+        let line = line.trim();
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        match args.first().copied().unwrap_or_default() {
+            "" | "clear" => {}
+            "exit" => return false,
+            "help" => println!("{}", help()),
+            "set" => {
+                if let Err(msg) = self.opt.set(&args[1..]) {
+                    eprintln!("{title} {msg}");
+                    return self.failed(USAGE_ERR);
+                }
+            }
+            "show-opt" | "show_opt" => self.opt.show_opt(line),
+            "vars" => show_vars(&self.env),
+            "history" => match args.get(1).copied() {
+                None => show_history(&self.env),
+                // only the shell keeps the typed lines, the results aren't affected
+                Some("clear") => eprintln!("there are no typed lines to forget outside the shell"),
+                Some(arg) => {
+                    eprintln!("{title} '{arg}' is not a valid argument!");
+                    return self.failed(USAGE_ERR);
+                }
+            },
+            "funcs" => show_funcs(&self.env),
+            _ => return self.solve(line, title),
+        }
+        true
+    }
+
+    /// returns false if the lines after it mustn't be evaluated
+    fn solve(&mut self, line: &str, title: &str) -> bool {
+        let mut steps = vec![];
+        let res = eval(line, &self.opt, &mut self.env, &mut steps);
+        if self.opt.output == Output::Json {
+            println!("{}", json::evaluation(line, &res, &steps, &self.opt));
+        } else {
+            show_steps(&steps, false);
+        }
+        match (res, self.opt.output) {
+            (Err(errs), Output::Text) => {
+                eprint!("{}", fmt_errors(line, &errs, title, false));
+                self.failed(exit_code(&errs[0]))
+            }
+            (Err(errs), Output::Json) => self.failed(exit_code(&errs[0])),
+            (Ok(Solved::Solution(res)), Output::Text) => {
+                println!("{}", fmt_solution(&res, &self.opt));
+                true
+            }
+            (Ok(_), _) => true,
+        }
+    }
+
+    /// keeps the exit code of the first failure, returns whether to keep going
+    pub fn failed(&mut self, code: u8) -> bool {
+        if self.code == 0 {
+            self.code = code;
+        }
+        self.keep_going
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(self.code)
+    }
+}
+
+/// every way res is shown on one line, as "7/3 = 2.(3)" or "≈ 314/100 ≈ 3.14"
+fn fmt_solution(res: &Value, opt: &Options) -> String {
+    let mut str = String::new();
+    for (_, exact, solution) in solutions(res, opt) {
+        str += match (str.is_empty(), exact) {
+            (true, true) => "",
+            (true, false) => "≈ ",
+            (false, true) => " = ",
+            (false, false) => " ≈ ",
+        };
+        str += &solution;
+    }
+    str
+}
+
+pub fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::Parse(_) => 2,
+        Error::Check(_) => 3,
+        Error::Solve(_) => 4,
+        Error::Spanned(err, _) => exit_code(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::{
+        error::{CheckErr, ParseErr, SolveErr},
+        solver::CheckRules,
+    };
+    use fraction::BigFraction;
+
+    #[test]
+    fn test_eval_lines() {
+        let lines = |lines: &[&str]| {
+            let lines = lines.iter().map(|line| Ok(line.to_string()));
+            lines.collect::<Vec<_>>()
+        };
+        let mut batch = Batch::new(Options::default(), false);
+        assert!(batch.eval_lines(lines(&["# x", "", "x = 3 # y", "set dec-len 2"]), None));
+        assert_eq!(batch.opt.dec_len, 2);
+        assert!(!batch.eval_lines(lines(&["x + y", "y = 1"]), Some("file")));
+        assert_eq!(batch.code, 4);
+        assert!(batch.env.vars().all(|(name, _)| name != "y"));
+        let mut batch = Batch::new(Options::default(), true);
+        assert!(batch.eval_lines(lines(&["set dec-len x", "1 +", "y = 1"]), None));
+        assert_eq!(batch.code, USAGE_ERR);
+        assert!(batch.env.vars().any(|(name, _)| name == "y"));
+        assert!(!batch.eval_lines(lines(&["exit", "z = 1"]), None));
+        assert!(batch.env.vars().all(|(name, _)| name != "z"));
+        let mut batch = Batch::new(Options::default(), true);
+        assert!(batch.eval_lines(lines(&["x"]), None));
+        assert!(batch.failed(USAGE_ERR));
+        assert_eq!(batch.code, 4);
+        assert!(!Batch::new(Options::default(), false).failed(USAGE_ERR));
+        let mut batch = Batch::new(Options::default(), false);
+        assert!(!batch.eval_expr("1 # 2"));
+        assert_eq!(batch.code, 2);
+        let mut batch = Batch::new(Options::default(), true);
+        assert!(batch.eval_lines(lines(&["1 + 1", "history clear", "ans"]), None));
+        assert_eq!(batch.code, 0);
+        assert_eq!(batch.env.history().len(), 2);
+        assert!(batch.eval_lines(lines(&["history foo"]), None));
+        assert_eq!(batch.code, USAGE_ERR);
+    }
+
+    #[test]
+    fn test_fmt_solution() {
+        let mut opt = Options::default();
+        let num = BigFraction::new(7u8, 3u8);
+        assert_eq!(
+            fmt_solution(&Value::exact(num.clone()), &opt),
+            "7/3 = 2.(3)"
+        );
+        opt.show_dec = false;
+        assert_eq!(fmt_solution(&Value::approx(num), &opt), "≈ 7/3");
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let parse = Error::from(ParseErr::InvalidToken(String::from("#")));
+        let check = Error::from(CheckErr::BrokenCheckRule(CheckRules::DenyDivision));
+        let solve = Error::from(SolveErr::UnknownVariable(String::from("x")));
+        assert_eq!(exit_code(&parse), 2);
+        assert_eq!(exit_code(&check), 3);
+        assert_eq!(exit_code(&solve.at(Some(Default::default()))), 4);
+    }
+}
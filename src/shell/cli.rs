@@ -0,0 +1,304 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use super::{
+    batch::{Batch, USAGE_ERR},
+    config, Options,
+};
+
+/// options set with "--name" or "--no-name"
+const FLAGS: [&str; 4] = ["show-dec", "explain", "approx", "overline"];
+
+const HINT: &str = "Try 'calculator --help' for more information.";
+
+const USAGE: &str = "Usage: calculator [options] [--] [expressions]
+
+Solves each expression and file line, printing each solution on one line,
+reads the lines from stdin if there are none and it isn't a terminal,
+opens the shell with the given options if there are none and stdin is a terminal.
+Options start from $XDG_CONFIG_HOME/calculator/config.toml, written by 'save-opt' in the shell.
+
+Options:
+  -h, --help                 => show this help message
+  -f, --file (path)          => solve each line of a file, '-' for stdin, '#' starts a comment
+  -k, --keep-going           => keep solving after an error
+  -c, --config (path)        => start from the options in another config file
+  --fail-fast                => stop at the first error (default)
+  --show-dec, --no-show-dec  => show/hide solution as a decimal value (also --dec, --no-dec)
+  --dec-len (integer)        => decimal solution precision
+  --checks (rules)           => change CheckRules, as in 'none', 'deny-op,-deny-exponent'
+  --fixes (rules)            => change FixRules, as in 'all', '-implicit-product'
+  --explain, --no-explain    => show/hide solution explanation (hidden outside the shell)
+  --approx, --no-approx      => approximate irrational solutions with dec-len digits
+  --out-base (integer)       => also show solution in a base from 2 to 36
+  --notation (fixed|sci|eng) => decimal solution as '12345.6', '1.23456e4' or '12.3456e3'
+  --sig-digits (integer)     => significant digits in sci and eng notation
+  --overline, --no-overline  => overline repeating digits, instead of '0.(3)'
+  --fraction (improper|mixed|continued) => fraction solution as '7/3', '2 1/3' or '[2; 3]'
+  --max-denom (integer)      => also show closest fraction with a smaller denominator, if not 0
+  --format (text|json)       => solution as text, or as one JSON object per line (also --output)
+  --history-size (integer)   => lines of the shell history kept across sessions
+  --name=value               => same as '--name value'
+
+Files and stdin can contain shell commands, as 'set dec-len 5' or 'exit'.
+
+Exit codes (of the first error):
+  0 => every expression was solved
+  1 => invalid arguments, unreadable files or failed commands
+  2 => ParseErr
+  3 => CheckErr
+  4 => SolveErr
+";
+
+/// where the lines to solve come from
+#[derive(Debug, PartialEq)]
+enum Input {
+    Expr(String),
+    /// path of the file, "-" for stdin
+    File(String),
+}
+
+#[derive(Debug)]
+struct Args {
+    /// arguments of "set" for each option, in order
+    sets: Vec<Vec<String>>,
+    inputs: Vec<Input>,
+    /// keep solving after an error
+    keep_going: bool,
+    /// config file to use instead of the default one
+    config: Option<PathBuf>,
+}
+
+impl Args {
+    /// opt changed by each option in the args
+    fn options(&self, mut opt: Options) -> Result<Options, String> {
+        for args in &self.sets {
+            opt.set(&args.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        Ok(opt)
+    }
+}
+
+/// solves the expressions and files in args with the options in args,
+/// or opens the shell if there are none to solve
+pub fn run(args: &[String]) -> ExitCode {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(msg) => {
+            eprintln!("{msg}\n{HINT}");
+            return ExitCode::from(USAGE_ERR);
+        }
+    };
+    let config = args.config.clone().or_else(config::path);
+    let opt = match &config {
+        Some(path) => config::load(path, args.config.is_some()),
+        None => Ok(Options::default()),
+    };
+    let opt = match opt {
+        Ok(opt) => opt,
+        Err(msg) => {
+            eprintln!("Error: {msg}");
+            return ExitCode::from(USAGE_ERR);
+        }
+    };
+    let shell = args.inputs.is_empty() && io::stdin().is_terminal();
+    let opt = Options {
+        // explanations would be mixed with the solutions of the other lines
+        explain: opt.explain && shell,
+        ..opt
+    };
+    let opt = match args.options(opt) {
+        Ok(opt) => opt,
+        Err(msg) => {
+            eprintln!("{msg}\n{HINT}");
+            return ExitCode::from(USAGE_ERR);
+        }
+    };
+    if shell {
+        super::run(opt, config);
+        return ExitCode::SUCCESS;
+    }
+    let Args {
+        mut inputs,
+        keep_going,
+        ..
+    } = args;
+    if inputs.is_empty() {
+        inputs.push(Input::File(String::from("-")));
+    }
+    let mut batch = Batch::new(opt, keep_going);
+    for input in inputs {
+        let keep_going = match input {
+            Input::Expr(expr) => batch.eval_expr(&expr),
+            Input::File(path) if path == "-" => {
+                batch.eval_lines(io::stdin().lines(), Some("stdin"))
+            }
+            Input::File(path) => match File::open(&path) {
+                Ok(file) => batch.eval_lines(BufReader::new(file).lines(), Some(&path)),
+                Err(err) => {
+                    eprintln!("can't read '{path}': {err}");
+                    batch.failed(USAGE_ERR)
+                }
+            },
+        };
+        if !keep_going {
+            break;
+        }
+    }
+    batch.exit_code()
+}
+
+/// options, expressions and files in args, or None if the help is asked
+fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut sets = Vec::new();
+    let mut inputs = Vec::new();
+    let mut keep_going = false;
+    let mut config = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // expressions can start with "-", as in "-3 + 4", but not with "--"
+        let flag = match arg.as_str() {
+            "-h" => "help",
+            "-f" => "file",
+            "-k" => "keep-going",
+            "-c" => "config",
+            _ => match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => {
+                    inputs.push(Input::Expr(arg.clone()));
+                    continue;
+                }
+            },
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (flag, None),
+        };
+        let name = match name {
+            "dec" => "show-dec",
+            "no-dec" => "no-show-dec",
+            "format" => "output",
+            _ => name,
+        };
+        let (name, value) = match (name, value) {
+            ("", None) => {
+                inputs.extend(args.cloned().map(Input::Expr));
+                break;
+            }
+            ("help", None) => return Ok(None),
+            ("keep-going", None) => {
+                keep_going = true;
+                continue;
+            }
+            ("fail-fast", None) => {
+                keep_going = false;
+                continue;
+            }
+            (name @ ("file" | "config"), value) => {
+                let path = match value {
+                    Some(path) => path,
+                    None => args.next().map(String::as_str).unwrap_or_default(),
+                };
+                match (path, name) {
+                    ("", _) => Err(format!("missing value of '--{name}'"))?,
+                    (path, "file") => inputs.push(Input::File(path.to_string())),
+                    (path, _) => config = Some(PathBuf::from(path)),
+                }
+                continue;
+            }
+            (name, None) if FLAGS.contains(&name) => (name, String::from("true")),
+            (name, None) => match name.strip_prefix("no-") {
+                Some(name) if FLAGS.contains(&name) => (name, String::from("false")),
+                _ => {
+                    let value = args.next().ok_or(format!("missing value of '--{name}'"))?;
+                    (name, value.clone())
+                }
+            },
+            (name, Some(value)) => (name, value.to_string()),
+        };
+        let values = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty());
+        let set_args = [name].into_iter().chain(values).collect::<Vec<_>>();
+        match set_args.as_slice() {
+            ["", ..] => Err(format!("'{arg}' is not a valid option!"))?,
+            [name] => Err(format!("missing value of '--{name}'"))?,
+            _ => sets.push(set_args.iter().map(|arg| arg.to_string()).collect()),
+        };
+    }
+    Ok(Some(Args {
+        sets,
+        inputs,
+        keep_going,
+        config,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::solver::{CheckRules, FixRules},
+        shell::Output,
+    };
+
+    /// args parsed, and the options they set starting from the default ones without explanations
+    fn parse(args: &[&str]) -> Result<Option<(Options, Args)>, String> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let Some(args) = parse_args(&args)? else {
+            return Ok(None);
+        };
+        let opt = Options {
+            explain: false,
+            ..Default::default()
+        };
+        Ok(Some((args.options(opt)?, args)))
+    }
+
+    #[test]
+    fn test_parse_args() -> Result<(), String> {
+        let expr = |str: &str| Input::Expr(String::from(str));
+        let file = |str: &str| Input::File(String::from(str));
+        let (opt, Args { inputs, .. }) =
+            parse(&["3/4 + 1/6", "--dec-len", "5", "-3", "--no-dec"])?.unwrap();
+        assert_eq!(inputs, [expr("3/4 + 1/6"), expr("-3")]);
+        assert_eq!((opt.dec_len, opt.show_dec, opt.explain), (5, false, false));
+        let (opt, Args { inputs, .. }) =
+            parse(&["--explain", "--checks=deny-op,-deny-exponent", "--", "--1"])?.unwrap();
+        assert_eq!(inputs, [expr("--1")]);
+        assert!(opt.explain);
+        let deny_op = CheckRules::DENY_OP
+            .into_iter()
+            .filter(|rule| rule != &CheckRules::DenyExponent);
+        assert_eq!(opt.checks, deny_op.collect::<Vec<_>>());
+        let (opt, _) = parse(&["--fixes", "none +close-blocks", "--notation", "sci"])?.unwrap();
+        assert_eq!(opt.fixes, [FixRules::CloseBlocks]);
+        let (opt, _) = parse(&["--format", "json"])?.unwrap();
+        assert_eq!(opt.output, Output::Json);
+        let (_, args) = parse(&["-f", "a.calc", "1", "-k", "--file=-"])?.unwrap();
+        assert_eq!(args.inputs, [file("a.calc"), expr("1"), file("-")]);
+        assert!(args.keep_going);
+        assert!(!parse(&["-k", "--fail-fast"])?.unwrap().1.keep_going);
+        let (_, args) = parse(&["-c", "my.toml", "1"])?.unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("my.toml")));
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["1", "--help"])?.is_none());
+        assert!(parse(&["-f"]).is_err());
+        assert!(parse(&["--file="]).is_err());
+        assert!(parse(&["--dec-len"]).is_err());
+        assert!(parse(&["--dec-len", "x"]).is_err());
+        assert!(parse(&["--dec-len="]).is_err());
+        assert!(parse(&["--no-dec-len"]).is_err());
+        assert!(parse(&["--foo", "1"]).is_err());
+        assert!(parse(&["--=1"]).is_err());
+        Ok(())
+    }
+}
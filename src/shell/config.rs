@@ -0,0 +1,218 @@
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use super::Options;
+use crate::common;
+
+/// default file of the options each session starts with
+pub fn path() -> Option<PathBuf> {
+    let (xdg, home) = (env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME"));
+    common::xdg_file(xdg, home, ".config", "config.toml")
+}
+
+/// options in the file at path, or the default ones if it doesn't exist and isn't required
+pub fn load(path: &Path, required: bool) -> Result<Options, String> {
+    let str = match fs::read_to_string(path) {
+        Ok(str) => str,
+        Err(err) if err.kind() == ErrorKind::NotFound && !required => return Ok(Options::default()),
+        Err(err) => return Err(format!("can't read '{}': {err}", path.display())),
+    };
+    parse(&str).map_err(|(line, msg)| format!("{}:{line}: {msg}", path.display()))
+}
+
+/// writes opt to the file at path, in the format read by load
+pub fn save(opt: &Options, path: &Path) -> Result<(), String> {
+    let err = |err| format!("can't write '{}': {err}", path.display());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(err)?;
+    }
+    fs::write(path, to_toml(opt)).map_err(err)
+}
+
+/// options set by each "name = value" line of str, starting from the default ones,
+/// or the line number and reason of the first invalid line
+fn parse(str: &str) -> Result<Options, (usize, String)> {
+    let mut opt = Options::default();
+    for (index, line) in str.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: String| (index + 1, msg);
+        let (name, value) = line
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .ok_or_else(|| err(format!("'{line}' is not 'option = value'!")))?;
+        let (name, value) = (name.trim(), value.trim());
+        let values =
+            parse_value(value).ok_or_else(|| err(format!("'{value}' is not a valid value!")))?;
+        let args = [name]
+            .into_iter()
+            .chain(values.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        opt.set(&args).map_err(err)?;
+    }
+    Ok(opt)
+}
+
+/// arguments of "set" for a value: "5" => ["5"], "\"deny-op -deny-exponent\"" =>
+/// ["deny-op", "-deny-exponent"], and an array of rules turns on only them,
+/// "[\"deny-division\"]" => ["none", "+deny-division"]
+fn parse_value(value: &str) -> Option<Vec<String>> {
+    let split = |str: &str| {
+        str.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    let values = if let Some(items) = value.strip_prefix('[') {
+        let items = items.strip_suffix(']')?.split(',').map(str::trim);
+        let names = items.filter(|item| !item.is_empty()).map(parse_string);
+        let rules = names.map(|name| Some(format!("+{}", name?)));
+        [Some(String::from("none"))]
+            .into_iter()
+            .chain(rules)
+            .collect::<Option<_>>()?
+    } else if value.starts_with('"') {
+        split(&parse_string(value)?)
+    } else {
+        split(value)
+    };
+    Some(values).filter(|values| !values.is_empty())
+}
+
+/// content of a quoted string, as "\"a\\\"b\"" => "a\"b"
+fn parse_string(str: &str) -> Option<String> {
+    let str = str.strip_prefix('"')?.strip_suffix('"')?;
+    let mut chars = str.chars();
+    let mut res = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => res.push(chars.next()?),
+            '"' => return None,
+            c => res.push(c),
+        }
+    }
+    Some(res)
+}
+
+/// line without its comment, if the "#" isn't in a string
+fn strip_comment(line: &str) -> &str {
+    let (mut quoted, mut escaped) = (false, false);
+    for (index, c) in line.char_indices() {
+        match c {
+            '#' if !quoted => return &line[..index],
+            '"' if !escaped => quoted = !quoted,
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    line
+}
+
+fn to_toml(opt: &Options) -> String {
+    let rules = |rules: Vec<String>| {
+        let rules = rules.iter().map(|rule| format!("\"{rule}\""));
+        format!("[{}]", rules.collect::<Vec<_>>().join(", "))
+    };
+    let name = |value: &dyn std::fmt::Debug| format!("\"{}\"", format!("{value:?}").to_lowercase());
+    let checks = rules(opt.checks.iter().map(ToString::to_string).collect());
+    let fixes = rules(opt.fixes.iter().map(ToString::to_string).collect());
+    format!(
+        "# options each session of the calculator starts with, written by 'save-opt'
+show-dec = {}
+dec-len = {}
+checks = {checks}
+fixes = {fixes}
+explain = {}
+approx = {}
+out-base = {}
+notation = {}
+sig-digits = {}
+overline = {}
+fraction = {}
+max-denom = {}
+output = {}
+history-size = {}
+",
+        opt.show_dec,
+        opt.dec_len,
+        opt.explain,
+        opt.approx,
+        opt.out_base,
+        name(&opt.notation),
+        opt.sig_digits,
+        opt.overline,
+        name(&opt.fraction),
+        opt.max_denom,
+        name(&opt.output),
+        opt.history_size,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::solver::{CheckRules, FixRules},
+        shell::{Notation, Output},
+    };
+
+    #[test]
+    fn test_parse() {
+        let opt = parse(
+            "# comment
+            dec_len = 5 # five digits
+
+            checks = [\"deny-division\", \"deny-exponent\"]
+            fixes = \"all -implicit-product\"
+            notation = \"sci\"
+            explain = false",
+        )
+        .unwrap();
+        assert_eq!(
+            (opt.dec_len, opt.notation, opt.explain),
+            (5, Notation::Sci, false)
+        );
+        let checks = [CheckRules::DenyDivision, CheckRules::DenyExponent];
+        assert_eq!(opt.checks, checks);
+        assert_eq!(opt.fixes, [FixRules::BlockProduct, FixRules::CloseBlocks]);
+        assert_eq!(parse("checks = []").unwrap().checks, []);
+        let line = |str: &str| parse(str).err().map(|(line, _)| line);
+        assert_eq!(line("dec-len = 101"), Some(1));
+        assert_eq!(line("\n\ndec-len = x"), Some(3));
+        assert_eq!(line("dec-len ="), Some(1));
+        assert_eq!(line("dec-len = \"\""), Some(1));
+        assert_eq!(line("= 5"), Some(1));
+        assert_eq!(line("[table]"), Some(1));
+        assert_eq!(line("foo = 1"), Some(1));
+        assert_eq!(line("checks = [deny-division]"), Some(1));
+        assert_eq!(line("notation = \"sci"), Some(1));
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let mut opt = Options::default();
+        let str = format!("{:?}", parse(&to_toml(&opt)).unwrap());
+        assert_eq!(str, format!("{opt:?}"));
+        opt.set(&["checks", "deny-op"]).unwrap();
+        opt.set(&["fixes", "none"]).unwrap();
+        opt.set(&["fraction", "mixed"]).unwrap();
+        opt.set(&["output", "json"]).unwrap();
+        opt.set(&["explain", "false"]).unwrap();
+        let str = format!("{:?}", parse(&to_toml(&opt)).unwrap());
+        assert_eq!(str, format!("{opt:?}"));
+        assert!(to_toml(&opt).contains("fixes = []\n"));
+        assert_eq!(parse(&to_toml(&opt)).unwrap().output, Output::Json);
+    }
+
+    #[test]
+    fn test_strip_comment() {
+        assert_eq!(strip_comment("a = 1 # b"), "a = 1 ");
+        assert_eq!(strip_comment("a = \"#\\\"#\" # b"), "a = \"#\\\"#\" ");
+        assert_eq!(strip_comment("a = 1"), "a = 1");
+    }
+}
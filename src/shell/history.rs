@@ -0,0 +1,115 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rustyline::{
+    config::Configurer,
+    history::{FileHistory, History},
+    DefaultEditor,
+};
+
+use crate::common;
+
+/// file of the lines typed in the shell, kept across sessions
+pub fn path() -> Option<PathBuf> {
+    let (xdg, home) = (env::var_os("XDG_DATA_HOME"), env::var_os("HOME"));
+    common::xdg_file(xdg, home, ".local/share", "history")
+}
+
+/// adds the lines of the previous sessions to the history of rl, if there are any
+pub fn load(rl: &mut DefaultEditor, path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let err = |err| format!("can't load history from '{}': {err}", path.display());
+    rl.load_history(path).map_err(err)
+}
+
+/// appends the new lines of rl to the ones saved at path, which other sessions may have
+/// added to meanwhile, keeping only the last copy of each line
+pub fn save(rl: &mut DefaultEditor, path: &Path) -> Result<(), String> {
+    let mut save = || -> rustyline::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        rl.append_history(path)?;
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut saved = FileHistory::new();
+        saved.set_max_len(rl.config_mut().max_history_size())?;
+        saved.load(path)?;
+        let lines = dedup(saved.iter().cloned().collect());
+        saved.clear()?;
+        for line in lines {
+            saved.add_owned(line)?;
+        }
+        saved.save(path)
+    };
+    save().map_err(|err| format!("can't save history to '{}': {err}", path.display()))
+}
+
+/// forgets the lines of this session, and of the previous ones if saved at path
+pub fn clear(rl: &mut DefaultEditor, path: Option<&Path>) -> Result<(), String> {
+    let mut clear = || -> rustyline::Result<()> {
+        rl.clear_history()?;
+        match path.map(fs::remove_file) {
+            Some(Err(err)) if err.kind() != ErrorKind::NotFound => Err(err)?,
+            _ => Ok(()),
+        }
+    };
+    clear().map_err(|err| format!("can't clear history: {err}"))
+}
+
+/// lines without the earlier copies of the repeated ones
+fn dedup(lines: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut lines = lines
+        .into_iter()
+        .rev()
+        .filter(|line| seen.insert(line.clone()))
+        .collect::<Vec<_>>();
+    lines.reverse();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup() {
+        let lines = |lines: &[&str]| {
+            lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+        let deduped = dedup(lines(&["1 + 1", "x = 2", "1 + 1", "x", "x"]));
+        assert_eq!(deduped, lines(&["x = 2", "1 + 1", "x"]));
+        assert!(dedup(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_save() -> Result<(), String> {
+        let path = env::temp_dir().join(format!("calculator-history-{}", std::process::id()));
+        let (mut rl1, mut rl2) = (DefaultEditor::new().unwrap(), DefaultEditor::new().unwrap());
+        for (rl, lines) in [(&mut rl1, ["1 + 1", "x = 2"]), (&mut rl2, ["3", "1 + 1"])] {
+            load(rl, &path)?;
+            for line in lines {
+                rl.add_history_entry(line).unwrap();
+            }
+        }
+        save(&mut rl1, &path)?;
+        save(&mut rl2, &path)?;
+        let mut rl = DefaultEditor::new().unwrap();
+        load(&mut rl, &path)?;
+        fs::remove_file(&path).unwrap();
+        let lines = rl.history().iter().cloned().collect::<Vec<_>>();
+        assert_eq!(lines, ["x = 2", "3", "1 + 1"]);
+        Ok(())
+    }
+}
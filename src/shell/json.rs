@@ -0,0 +1,139 @@
+use fraction::{Sign, Zero};
+
+use super::{Options, Solved};
+use crate::{
+    common::algs,
+    expression::{error::Error, value::Value},
+};
+
+/// one JSON object with the line, what it defined or its solution, the explanation steps
+/// and every error found, with null in place of what is missing
+pub fn evaluation(
+    line: &str,
+    res: &Result<Solved, Vec<Error>>,
+    steps: &[String],
+    opt: &Options,
+) -> String {
+    let null = || String::from("null");
+    let (defined, solution, errors) = match res {
+        Ok(Solved::Defined(name)) => (string(name), null(), array([])),
+        Ok(Solved::Solution(res)) => (null(), solution(res, opt), array([])),
+        Err(errs) => (null(), null(), array(errs.iter().map(error))),
+    };
+    let steps = array(steps.iter().map(|step| string(step)));
+    let input = string(line);
+    format!(
+        "{{\"input\":{input},\"defined\":{defined},\"solution\":{solution},\"steps\":{steps},\"errors\":{errors}}}"
+    )
+}
+
+/// the exact numerator and denominator as strings, the sign as -1, 0 or 1,
+/// and the fraction and decimal as shown with opt
+fn solution(res: &Value, opt: &Options) -> String {
+    let ratio = algs::to_ratio(&res.num);
+    let numer = ratio
+        .as_ref()
+        .map(|ratio| string(&ratio.numer().to_string()));
+    let denom = ratio
+        .as_ref()
+        .map(|ratio| string(&ratio.denom().to_string()));
+    let sign = match (res.num.sign(), res.num.is_zero()) {
+        (_, true) => 0,
+        (Some(Sign::Minus), _) => -1,
+        _ => 1,
+    };
+    format!(
+        "{{\"numerator\":{},\"denominator\":{},\"sign\":{sign},\"exact\":{},\"fraction\":{},\"decimal\":{}}}",
+        numer.unwrap_or_else(|| String::from("null")),
+        denom.unwrap_or_else(|| String::from("null")),
+        res.exact,
+        string(&opt.as_fraction(&res.num)),
+        string(&opt.as_decimal(&res.num)),
+    )
+}
+
+/// the kind, variant, message, tokens and byte range of err
+fn error(err: &Error) -> String {
+    let span = match err.span() {
+        Some(span) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+        None => String::from("null"),
+    };
+    format!(
+        "{{\"kind\":{},\"variant\":{},\"message\":{},\"tokens\":{},\"span\":{span}}}",
+        string(err.kind()),
+        string(err.variant()),
+        string(&err.to_string()),
+        array(err.tokens().iter().map(|token| string(token))),
+    )
+}
+
+fn array(items: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+fn string(str: &str) -> String {
+    let mut json = String::from('"');
+    for c in str.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\r' => json += "\\r",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::env::Env;
+    use crate::shell::eval;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string("1 + 2"), "\"1 + 2\"");
+        assert_eq!(string("\"a\\b\"\n\u{1}≈"), "\"\\\"a\\\\b\\\"\\n\\u0001≈\"");
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let opt = Options {
+            explain: true,
+            ..Default::default()
+        };
+        let json = |line: &str, env: &mut Env| {
+            let mut steps = vec![];
+            let res = eval(line, &opt, env, &mut steps);
+            evaluation(line, &res, &steps, &opt)
+        };
+        let env = &mut Env::default();
+        assert_eq!(
+            json("-1/4 - 1/12", env),
+            concat!(
+                r#"{"input":"-1/4 - 1/12","defined":null,"#,
+                r#""solution":{"numerator":"1","denominator":"3","sign":-1,"exact":true,"#,
+                r#""fraction":"-1/3","decimal":"-0.(3)"},"#,
+                r#""steps":["- 1 / 4 - 1 / 12","-1 / 4 - 1 / 12","-1/4 - 1 / 12","-1/4 - 1/12","-1/3"],"#,
+                r#""errors":[]}"#
+            )
+        );
+        assert_eq!(
+            json("f(x) = x", env),
+            r#"{"input":"f(x) = x","defined":"f","solution":null,"steps":[],"errors":[]}"#
+        );
+        assert_eq!(
+            json("1 + y", env),
+            concat!(
+                r#"{"input":"1 + y","defined":null,"solution":null,"steps":["1 + y"],"#,
+                r#""errors":[{"kind":"SolveErr","variant":"UnknownVariable","#,
+                r#""message":"SolveErr: unknown variable 'y'","tokens":["y"],"#,
+                r#""span":{"start":4,"end":5}}]}"#
+            )
+        );
+    }
+}
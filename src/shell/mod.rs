@@ -1,11 +1,23 @@
-use std::process::Command;
+mod batch;
+pub mod cli;
+mod config;
+mod history;
+mod json;
 
-use fraction::BigFraction;
-use rustyline::{error::ReadlineError, DefaultEditor};
+use std::{fmt::Display, path::PathBuf, process::Command};
+
+use fraction::{BigFraction, BigUint};
+use rustyline::{config::Configurer, error::ReadlineError, Config, DefaultEditor};
 
 use crate::{
-    common::{self, Color},
-    expression::solver::{self, CheckRules, FixRules},
+    common::{self, algs, Color},
+    expression::{
+        env::Env,
+        error::Error,
+        function::{Builtin, Constant},
+        solver::{self, CheckRules, FixRules},
+        value::Value,
+    },
 };
 
 #[derive(Debug)]
@@ -20,6 +32,52 @@ struct Options {
     fixes: Vec<FixRules>,
     /// explain solver steps
     explain: bool,
+    /// approximate irrational results with dec_len digits
+    approx: bool,
+    /// base of the additional result, shown if it isn't 10
+    out_base: u32,
+    /// notation of the decimal result
+    notation: Notation,
+    /// significant digits of the decimal result, in scientific or engineering notation
+    sig_digits: u64,
+    /// overline the repeating digits, instead of putting them between brackets
+    overline: bool,
+    /// format of the fraction result
+    fraction: FracFormat,
+    /// biggest denominator of the additional approximated result, not shown if 0
+    max_denom: u64,
+    /// format of everything shown about an evaluation
+    output: Output,
+    /// lines of the shell history kept across sessions
+    history_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// "Solution (fraction): 7/3"
+    Text,
+    /// one JSON object per evaluation
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FracFormat {
+    /// "7/3"
+    Improper,
+    /// "2 1/3"
+    Mixed,
+    /// continued fraction "[2; 3]"
+    Continued,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notation {
+    /// "12345.6", with at most dec_len digits after the decimal point and the repeating ones as "0.(3)"
+    Fixed,
+    /// "1.23456e4"
+    Sci,
+    /// "12.3456e3", with an exponent multiple of 3
+    Eng,
 }
 
 impl Default for Options {
@@ -30,16 +88,35 @@ impl Default for Options {
             checks: vec![],
             fixes: FixRules::ALL.to_vec(),
             explain: true,
+            approx: true,
+            out_base: 10,
+            notation: Notation::Fixed,
+            sig_digits: 10,
+            overline: false,
+            fraction: FracFormat::Improper,
+            max_denom: 0,
+            output: Output::Text,
+            history_size: 1000,
         }
     }
 }
 
 impl Options {
     const MAX_DEC_LEN: u64 = 100;
+    const MAX_HISTORY_SIZE: usize = 100_000;
+    const OUT_BASES: std::ops::RangeInclusive<u32> = 2..=36;
 
     fn change(&mut self, line: &str) {
-        let default = Default::default();
         let args = line.split_whitespace().skip(1).collect::<Vec<_>>();
+        match self.set(&args) {
+            Ok(msg) => suc(msg),
+            Err(msg) => err(msg),
+        }
+    }
+
+    /// changes the option args[0] to the values after it, returning what changed
+    fn set(&mut self, args: &[&str]) -> Result<String, String> {
+        let default = Default::default();
         let opt = *args.first().unwrap_or(&"");
         let value = *args.get(1).unwrap_or(&"");
         let opt_err = format!("'{opt}' is not a valid option!");
@@ -47,97 +124,253 @@ impl Options {
         match opt {
             "" => {
                 *self = default;
-                suc(String::from("successfully resetted all options"));
+                Ok(String::from("successfully resetted all options"))
             }
             "show_dec" | "show-dec" => match value {
                 "" => {
                     self.show_dec = default.show_dec;
-                    suc(String::from("successfully resetted 'show-dec'"));
+                    Ok(String::from("successfully resetted 'show-dec'"))
                 }
                 "true" => {
                     self.show_dec = true;
-                    suc(String::from("successfully setted 'show-dec' to true"));
+                    Ok(String::from("successfully setted 'show-dec' to true"))
                 }
                 "false" => {
                     self.show_dec = false;
-                    suc(String::from("successfully setted 'show-dec' to false"));
+                    Ok(String::from("successfully setted 'show-dec' to false"))
                 }
-                _ => err(value_err),
+                _ => Err(value_err),
             },
             "dec_len" | "dec-len" => match value {
                 "" => {
                     self.dec_len = default.dec_len;
-                    suc(String::from("successfully resetted 'dec-len'"));
+                    Ok(String::from("successfully resetted 'dec-len'"))
                 }
                 _ => {
                     let parsed = args.get(1).unwrap_or(&"").parse::<u64>();
                     match parsed {
                         Ok(value) => {
                             if value > Self::MAX_DEC_LEN {
-                                err(format!("{value} is too big!"));
+                                Err(format!("{value} is too big!"))
                             } else {
                                 self.dec_len = value;
-                                suc(format!("successfully setted 'dec_len' to {value}"));
+                                Ok(format!("successfully setted 'dec_len' to {value}"))
                             }
                         }
-                        Err(_) => err(value_err),
+                        Err(_) => Err(value_err),
                     }
                 }
             },
             "checks" => match value {
                 "" => {
                     self.checks = default.checks;
-                    suc(String::from("successfully resetted 'checks'"));
-                }
-                "none" => {
-                    self.checks = vec![];
-                    suc(String::from("successfully setted 'checks' to none"));
-                }
-                "all" => {
-                    self.checks = CheckRules::ALL.to_vec();
-                    suc(String::from("successfully setted 'checks' to all"));
-                }
-                "deny-op" | "deny_op" => {
-                    self.checks = CheckRules::DENY_OP.to_vec();
-                    suc(String::from("successfully setted 'checks' to deny-op"));
+                    Ok(String::from("successfully resetted 'checks'"))
                 }
-                "deny-sign" | "deny_sign" => {
-                    self.checks = CheckRules::DENY_SIGN.to_vec();
-                    suc(String::from("successfully setted 'checks' to deny-sign"));
+                _ => {
+                    let preset = |value: &str| match value {
+                        "none" => Some(vec![]),
+                        "all" => Some(CheckRules::ALL.to_vec()),
+                        "deny-op" => Some(CheckRules::DENY_OP.to_vec()),
+                        "deny-sign" => Some(CheckRules::DENY_SIGN.to_vec()),
+                        _ => None,
+                    };
+                    let rules = &CheckRules::ALL;
+                    match change_rules(
+                        &self.checks,
+                        &args[1..],
+                        rules,
+                        preset,
+                        CheckRules::from_name,
+                    ) {
+                        Ok(checks) => {
+                            self.checks = checks;
+                            let checks = fmt_rules(&self.checks);
+                            Ok(format!("successfully setted 'checks' to {checks}"))
+                        }
+                        Err(value) => Err(format!("'{value}' is not a valid value!")),
+                    }
                 }
-                _ => err(value_err),
             },
             "fixes" => match value {
                 "" => {
                     self.fixes = default.fixes;
-                    suc(String::from("successfully resetted 'fixes'"));
-                }
-                "none" => {
-                    self.fixes = vec![];
-                    suc(String::from("successfully setted 'fixes' to none"));
+                    Ok(String::from("successfully resetted 'fixes'"))
                 }
-                "all" => {
-                    self.fixes = FixRules::ALL.to_vec();
-                    suc(String::from("successfully setted 'fixes' to all"));
+                _ => {
+                    let preset = |value: &str| match value {
+                        "none" => Some(vec![]),
+                        "all" => Some(FixRules::ALL.to_vec()),
+                        _ => None,
+                    };
+                    let rules = &FixRules::ALL;
+                    match change_rules(&self.fixes, &args[1..], rules, preset, FixRules::from_name)
+                    {
+                        Ok(fixes) => {
+                            self.fixes = fixes;
+                            let fixes = fmt_rules(&self.fixes);
+                            Ok(format!("successfully setted 'fixes' to {fixes}"))
+                        }
+                        Err(value) => Err(format!("'{value}' is not a valid value!")),
+                    }
                 }
-                _ => err(value_err),
             },
             "explain" => match value {
                 "" => {
                     self.explain = default.explain;
-                    suc(String::from("successfully resetted 'explain'"));
+                    Ok(String::from("successfully resetted 'explain'"))
                 }
                 "true" => {
                     self.explain = true;
-                    suc(String::from("successfully setted 'explain' to true"));
+                    Ok(String::from("successfully setted 'explain' to true"))
                 }
                 "false" => {
                     self.explain = false;
-                    suc(String::from("successfully setted 'explain' to false"));
+                    Ok(String::from("successfully setted 'explain' to false"))
+                }
+                _ => Err(value_err),
+            },
+            "approx" => match value {
+                "" => {
+                    self.approx = default.approx;
+                    Ok(String::from("successfully resetted 'approx'"))
+                }
+                "true" => {
+                    self.approx = true;
+                    Ok(String::from("successfully setted 'approx' to true"))
+                }
+                "false" => {
+                    self.approx = false;
+                    Ok(String::from("successfully setted 'approx' to false"))
                 }
-                _ => err(value_err),
+                _ => Err(value_err),
+            },
+            "out_base" | "out-base" => match value {
+                "" => {
+                    self.out_base = default.out_base;
+                    Ok(String::from("successfully resetted 'out-base'"))
+                }
+                _ => match value.parse::<u32>() {
+                    Ok(value) if Self::OUT_BASES.contains(&value) => {
+                        self.out_base = value;
+                        Ok(format!("successfully setted 'out-base' to {value}"))
+                    }
+                    Ok(value) => Err(format!("{value} is not a base between 2 and 36!")),
+                    Err(_) => Err(value_err),
+                },
             },
-            _ => err(opt_err),
+            "notation" => match value {
+                "" => {
+                    self.notation = default.notation;
+                    Ok(String::from("successfully resetted 'notation'"))
+                }
+                "fixed" => {
+                    self.notation = Notation::Fixed;
+                    Ok(String::from("successfully setted 'notation' to fixed"))
+                }
+                "sci" => {
+                    self.notation = Notation::Sci;
+                    Ok(String::from("successfully setted 'notation' to sci"))
+                }
+                "eng" => {
+                    self.notation = Notation::Eng;
+                    Ok(String::from("successfully setted 'notation' to eng"))
+                }
+                _ => Err(value_err),
+            },
+            "sig_digits" | "sig-digits" => match value {
+                "" => {
+                    self.sig_digits = default.sig_digits;
+                    Ok(String::from("successfully resetted 'sig-digits'"))
+                }
+                _ => match value.parse::<u64>() {
+                    Ok(0) => Err(String::from("0 is too small!")),
+                    Ok(value) if value > Self::MAX_DEC_LEN => Err(format!("{value} is too big!")),
+                    Ok(value) => {
+                        self.sig_digits = value;
+                        Ok(format!("successfully setted 'sig-digits' to {value}"))
+                    }
+                    Err(_) => Err(value_err),
+                },
+            },
+            "overline" => match value {
+                "" => {
+                    self.overline = default.overline;
+                    Ok(String::from("successfully resetted 'overline'"))
+                }
+                "true" => {
+                    self.overline = true;
+                    Ok(String::from("successfully setted 'overline' to true"))
+                }
+                "false" => {
+                    self.overline = false;
+                    Ok(String::from("successfully setted 'overline' to false"))
+                }
+                _ => Err(value_err),
+            },
+            "fraction" => match value {
+                "" => {
+                    self.fraction = default.fraction;
+                    Ok(String::from("successfully resetted 'fraction'"))
+                }
+                "improper" => {
+                    self.fraction = FracFormat::Improper;
+                    Ok(String::from("successfully setted 'fraction' to improper"))
+                }
+                "mixed" => {
+                    self.fraction = FracFormat::Mixed;
+                    Ok(String::from("successfully setted 'fraction' to mixed"))
+                }
+                "continued" => {
+                    self.fraction = FracFormat::Continued;
+                    Ok(String::from("successfully setted 'fraction' to continued"))
+                }
+                _ => Err(value_err),
+            },
+            "max_denom" | "max-denom" => match value {
+                "" => {
+                    self.max_denom = default.max_denom;
+                    Ok(String::from("successfully resetted 'max-denom'"))
+                }
+                _ => match value.parse::<u64>() {
+                    Ok(value) => {
+                        self.max_denom = value;
+                        Ok(format!("successfully setted 'max-denom' to {value}"))
+                    }
+                    Err(_) => Err(value_err),
+                },
+            },
+            "output" => match value {
+                "" => {
+                    self.output = default.output;
+                    Ok(String::from("successfully resetted 'output'"))
+                }
+                "text" => {
+                    self.output = Output::Text;
+                    Ok(String::from("successfully setted 'output' to text"))
+                }
+                "json" => {
+                    self.output = Output::Json;
+                    Ok(String::from("successfully setted 'output' to json"))
+                }
+                _ => Err(value_err),
+            },
+            "history_size" | "history-size" => match value {
+                "" => {
+                    self.history_size = default.history_size;
+                    Ok(String::from("successfully resetted 'history-size'"))
+                }
+                _ => match value.parse::<usize>() {
+                    Ok(value) if value > Self::MAX_HISTORY_SIZE => {
+                        Err(format!("{value} is too big!"))
+                    }
+                    Ok(value) => {
+                        self.history_size = value;
+                        Ok(format!("successfully setted 'history-size' to {value}"))
+                    }
+                    Err(_) => Err(value_err),
+                },
+            },
+            _ => Err(opt_err),
         }
     }
 
@@ -147,60 +380,123 @@ impl Options {
         match value {
             "show-dec" | "show_dec" => println!("show-dec is '{}'", self.show_dec),
             "dec-len" | "dec_len" => println!("dec-len is '{}'", self.dec_len),
-            "checks" => println!("checks is '{:?}'", self.checks),
-            "fixes" => println!("fixes is '{:?}'", self.fixes),
+            "checks" => show_rules("checks", &CheckRules::ALL, &self.checks),
+            "fixes" => show_rules("fixes", &FixRules::ALL, &self.fixes),
             "explain" => println!("explain is '{:?}'", self.explain),
+            "approx" => println!("approx is '{:?}'", self.approx),
+            "out-base" | "out_base" => println!("out-base is '{}'", self.out_base),
+            "notation" => println!("notation is '{:?}'", self.notation),
+            "sig-digits" | "sig_digits" => println!("sig-digits is '{}'", self.sig_digits),
+            "overline" => println!("overline is '{}'", self.overline),
+            "fraction" => println!("fraction is '{:?}'", self.fraction),
+            "max-denom" | "max_denom" => println!("max-denom is '{}'", self.max_denom),
+            "output" => println!("output is '{:?}'", self.output),
+            "history-size" | "history_size" => {
+                println!("history-size is '{}'", self.history_size)
+            }
             _ => {
                 self.show_opt("show_opt show-dec");
                 self.show_opt("show_opt dec-len");
                 self.show_opt("show_opt checks");
                 self.show_opt("show_opt fixes");
                 self.show_opt("show_opt explain");
+                self.show_opt("show_opt approx");
+                self.show_opt("show_opt out-base");
+                self.show_opt("show_opt notation");
+                self.show_opt("show_opt sig-digits");
+                self.show_opt("show_opt overline");
+                self.show_opt("show_opt fraction");
+                self.show_opt("show_opt max-denom");
+                self.show_opt("show_opt output");
+                self.show_opt("show_opt history-size");
             }
         }
     }
 
     fn as_decimal(&self, num: &BigFraction) -> String {
         let prec = self.dec_len as usize;
-        format!("{:.prec$}", num)
+        let sig_digits = self.sig_digits as usize;
+        let str = match self.notation {
+            Notation::Fixed => algs::as_base(num, 10, prec, self.overline),
+            Notation::Sci => algs::as_scientific(num, sig_digits, false),
+            Notation::Eng => algs::as_scientific(num, sig_digits, true),
+        };
+        str.unwrap_or_else(|| num.to_string())
+    }
+
+    fn as_fraction(&self, num: &BigFraction) -> String {
+        let str = match self.fraction {
+            FracFormat::Improper => Some(num.to_string()),
+            FracFormat::Mixed => algs::as_mixed(num),
+            FracFormat::Continued => algs::as_continued(num),
+        };
+        str.unwrap_or_else(|| num.to_string())
+    }
+
+    fn as_base(&self, num: &BigFraction) -> Option<String> {
+        algs::as_base(num, self.out_base, self.dec_len as usize, self.overline)
     }
 }
 
-pub fn run() {
-    let mut rl = DefaultEditor::new().unwrap();
-    let mut opt: Options = Default::default();
+/// opens the shell, where "save-opt" writes the options to config_path
+fn run(mut opt: Options, config_path: Option<PathBuf>) {
+    let mut env = Env::default();
+    let editor = Config::builder()
+        .max_history_size(opt.history_size)
+        .and_then(|editor| editor.history_ignore_dups(true))
+        .map(|editor| editor.build());
+    let mut rl = match editor.and_then(DefaultEditor::with_config) {
+        Ok(rl) => rl,
+        Err(err) => return println!("Error: {:?}", err),
+    };
+    let history_path = history::path();
+    if let Some(Err(msg)) = history_path
+        .as_ref()
+        .map(|path| history::load(&mut rl, path))
+    {
+        err(msg);
+    }
     loop {
         let readline = rl.readline(&common::color(&Color::OTH, ">>> "));
         match readline {
             Ok(line) => {
-                rl.add_history_entry(line.as_str()).unwrap();
+                // a line missing from the history isn't worth stopping the shell
+                let _ = rl.add_history_entry(line.as_str());
                 match line.split_whitespace().next().unwrap_or_default() {
                     "" => continue,
                     "exit" => break,
                     "clear" => {
-                        Command::new("clear").spawn().unwrap().wait().unwrap();
+                        if let Err(err) = Command::new("clear").status() {
+                            println!("Error: {:?}", err);
+                        }
                     }
                     "help" => println!("{}", help()),
-                    "set" => opt.change(&line),
-                    "show-opt" | "show_opt" => opt.show_opt(&line),
-                    _ => match solver::resolve(&line, &opt.fixes, &opt.checks, opt.explain) {
-                        Ok(res) => {
-                            let title = common::color(&Color::TIT, "Solution (fraction):");
-                            let res_str = common::color(&Color::SUC, &res);
-                            println!("{title} {res_str}");
-                            if opt.show_dec {
-                                let title = common::color(&Color::TIT, "Solution (decimal):");
-                                let res_str = common::color(&Color::SUC, &opt.as_decimal(&res));
-                                println!("{title} {res_str}");
-                            }
-                            println!();
-                        }
-                        Err(err) => {
-                            let title = common::color(&Color::TIT, "Error:");
-                            let err = common::color(&Color::FAI, &err);
-                            println!("{title} {err}\n");
+                    "set" => {
+                        opt.change(&line);
+                        if let Err(err) = rl.set_max_history_size(opt.history_size) {
+                            println!("Error: {:?}", err);
                         }
+                    }
+                    "show-opt" | "show_opt" => opt.show_opt(&line),
+                    "save-opt" | "save_opt" => match config_path
+                        .as_deref()
+                        .map(|path| config::save(&opt, path))
+                    {
+                        Some(Ok(())) => suc(String::from("successfully saved options")),
+                        Some(Err(msg)) => err(msg),
+                        None => err(String::from("there is no config file to save options to!")),
+                    },
+                    "vars" => show_vars(&env),
+                    "history" => match line.split_whitespace().nth(1) {
+                        None => show_history(&env),
+                        Some("clear") => match history::clear(&mut rl, history_path.as_deref()) {
+                            Ok(()) => suc(String::from("successfully cleared the typed lines")),
+                            Err(msg) => err(msg),
+                        },
+                        Some(arg) => err(format!("'{arg}' is not a valid argument!")),
                     },
+                    "funcs" => show_funcs(&env),
+                    _ => solve(&line, &opt, &mut env),
                 }
             }
             Err(ReadlineError::Interrupted) => {
@@ -215,28 +511,245 @@ pub fn run() {
             }
         }
     }
+    if let Some(Err(msg)) = history_path.map(|path| history::save(&mut rl, &path)) {
+        err(msg);
+    }
+}
+
+/// what solving a line resulted in
+enum Solved {
+    /// name of the variable or function defined
+    Defined(String),
+    Solution(Value),
+}
+
+/// defines or solves line, showing the result or every error found
+fn solve(line: &str, opt: &Options, env: &mut Env) {
+    let mut steps = vec![];
+    let res = eval(line, opt, env, &mut steps);
+    if opt.output == Output::Json {
+        return println!("{}", json::evaluation(line, &res, &steps, opt));
+    }
+    show_steps(&steps, true);
+    match res {
+        Ok(Solved::Defined(name)) => suc(format!("successfully defined '{name}'\n")),
+        Ok(Solved::Solution(res)) => show_solution(&res, opt),
+        Err(errs) => fail(line, &errs),
+    }
+}
+
+/// defines or solves line, pushing each step of the explanation to steps if opt.explain
+fn eval(
+    line: &str,
+    opt: &Options,
+    env: &mut Env,
+    steps: &mut Vec<String>,
+) -> Result<Solved, Vec<Error>> {
+    if let Some(name) = solver::define_all(line, env, &opt.fixes, &opt.checks)? {
+        return Ok(Solved::Defined(name));
+    }
+    let (fixes, checks) = (&opt.fixes, &opt.checks);
+    let prec = opt.approx.then_some(opt.dec_len);
+    let res = match opt.explain {
+        true => solver::resolve_steps(line, env, fixes, checks, prec, steps)?,
+        false => solver::resolve_all(line, env, fixes, checks, prec, false)?,
+    };
+    Ok(Solved::Solution(res))
+}
+
+/// shows the explanation steps, with a colored title if colored
+fn show_steps(steps: &[String], colored: bool) {
+    if !steps.is_empty() {
+        let title = paint(&Color::TIT, "Explanation:", colored);
+        println!("{title}\n{}", steps.join("\n"));
+    }
+}
+
+fn show_solution(res: &Value, opt: &Options) {
+    for (title, exact, str) in solutions(res, opt) {
+        // approximated results are marked, so they aren't mistaken for exact ones
+        let mark = if exact { "" } else { "≈ " };
+        let title = common::color(&Color::TIT, &format!("Solution ({title}):"));
+        let res_str = common::color(&Color::SUC, &format!("{mark}{str}"));
+        println!("{title} {res_str}");
+    }
+    println!();
+}
+
+/// title, exactness and text of each way res is shown with opt
+fn solutions(res: &Value, opt: &Options) -> Vec<(String, bool, String)> {
+    let frac = opt.as_fraction(&res.num);
+    let mut solutions = vec![(String::from("fraction"), res.exact, frac)];
+    if opt.show_dec {
+        let dec = opt.as_decimal(&res.num);
+        solutions.push((String::from("decimal"), res.exact, dec));
+    }
+    if let Some(str) = opt.as_base(&res.num).filter(|_| opt.out_base != 10) {
+        solutions.push((format!("base {}", opt.out_base), res.exact, str));
+    }
+    if let Some(best) = algs::best_approx(&res.num, &BigUint::from(opt.max_denom)) {
+        let title = format!("denominator <= {}", opt.max_denom);
+        let exact = res.exact && best == res.num;
+        solutions.push((title, exact, opt.as_fraction(&best)));
+    }
+    solutions
+}
+
+/// shows every error, underlining the part of line which caused it
+fn fail(line: &str, errs: &[Error]) {
+    println!("{}", fmt_errors(line, errs, "Error:", true));
+}
+
+/// every error with the part of line which caused it underlined, colored if colored
+fn fmt_errors(line: &str, errs: &[Error], title: &str, colored: bool) -> String {
+    let mut str = String::new();
+    for err in errs {
+        let title = paint(&Color::TIT, title, colored);
+        let msg = paint(&Color::FAI, err, colored);
+        str += &format!("{title} {msg}\n");
+        if let Some(underline) = err.underline(line) {
+            str += &format!("  {line}\n  {}\n", paint(&Color::FAI, &underline, colored));
+        }
+    }
+    str
+}
+
+/// str with color if colored, which batch output never is, even on a terminal
+fn paint<T: Display + ?Sized>(color: &Color, str: &T, colored: bool) -> String {
+    match colored {
+        true => common::color(color, str),
+        false => str.to_string(),
+    }
+}
+
+/// rules resulting from each of args, in the order of all:
+/// a preset replaces the current ones, "+rule" turns one on and "-rule" turns it off,
+/// returns the first invalid arg, leaving current unchanged
+fn change_rules<'a, T: Clone + PartialEq>(
+    current: &[T],
+    args: &[&'a str],
+    all: &[T],
+    preset: impl Fn(&str) -> Option<Vec<T>>,
+    from_name: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, &'a str> {
+    let mut rules = current.to_vec();
+    for arg in args {
+        let name = arg.replace('_', "-");
+        let (on, name) = match (name.strip_prefix('+'), name.strip_prefix('-')) {
+            (Some(name), _) => (true, name),
+            (_, Some(name)) => (false, name),
+            _ => {
+                rules = preset(&name).ok_or(*arg)?;
+                continue;
+            }
+        };
+        let rule = from_name(name).ok_or(*arg)?;
+        rules.retain(|other| other != &rule);
+        if on {
+            rules.push(rule);
+        }
+    }
+    Ok(all
+        .iter()
+        .filter(|rule| rules.contains(rule))
+        .cloned()
+        .collect())
+}
+
+fn fmt_rules<T: Display>(rules: &[T]) -> String {
+    match rules {
+        [] => String::from("none"),
+        _ => common::fmt(rules, Some(", ")),
+    }
+}
+
+/// shows whether each rule is on or off
+fn show_rules<T: Display + PartialEq>(opt: &str, all: &[T], rules: &[T]) {
+    println!("{opt} is '{}'", fmt_rules(rules));
+    for rule in all {
+        let state = if rules.contains(rule) { "on" } else { "off" };
+        println!("  - {rule} is '{state}'");
+    }
 }
 
 fn help() -> String {
-    String::from(
+    let builtins = common::fmt(&Builtin::ALL, Some(", "));
+    let constants = common::fmt(&Constant::ALL, Some(", "));
+    let checks = common::fmt(&CheckRules::ALL, Some(", "));
+    let fixes = common::fmt(&FixRules::ALL, Some(", "));
+    format!(
         "Commands:
   - exit                => close shell
   - clear               => clear terminal
   - help                => show this help message
   - set  [opt] [value]  => change options
   - show-opt  [opt]     => show current options
+  - save-opt            => save current options, which the next sessions start with
+  - vars                => show defined variables
+  - history             => show previous results ('ans' is the last, '$1' the first)
+  - history clear       => forget the lines typed in this and the previous sessions, not the results
+  - funcs               => show defined functions
+  - [name] = *          => parse as an expression and store it in a variable
+  - [name]([args]) = *  => define a function, callable as 'name(values)'
   - *                   => parse as an expression
 
      [opt]     [value]      
   - show-dec [true|false]                       => show/hide solution as a decimal value
   - dec-len  [(integer)]                        => decimal solution precision
-  - checks   [none|all|deny-sign|deny-op]       => change CheckRules
-  - fixes    [none|all]                         => change FixRules
+  - checks   [none|all|deny-sign|deny-op|+rule|-rule] => change CheckRules, '+'/'-' turns a rule on/off
+  - fixes    [none|all|+rule|-rule]             => change FixRules, '+'/'-' turns a rule on/off
   - explain  [true|false]                       => show/hide solution explanation
+  - approx   [true|false]                       => approximate irrational solutions with dec-len digits
+  - out-base [(integer)]                        => also show solution in a base from 2 to 36
+  - notation [fixed|sci|eng]                    => decimal solution as '12345.6', '1.23456e4' or '12.3456e3'
+  - sig-digits [(integer)]                      => significant digits in sci and eng notation
+  - overline [true|false]                       => overline repeating digits, instead of '0.(3)'
+  - fraction [improper|mixed|continued]         => fraction solution as '7/3', '2 1/3' or '[2; 3]'
+  - max-denom [(integer)]                       => also show closest fraction with a smaller denominator, if not 0
+  - output   [text|json]                        => solution as text, or as one JSON object per evaluation
+  - history-size [(integer)]                    => lines of the shell history kept across sessions
+
+Functions:
+  - {builtins}
+
+Constants:
+  - {constants}
+
+Check rules:
+  - {checks}
+
+Fix rules:
+  - {fixes}
+
+Operators (from highest to lowest precedence):
+  - x!          => factorial
+  - x^y         => exponent, right associative: '2^3^2' is '2^(3^2)'
+  - -x +x !x    => sign and derangement: '-2^2' is '-(2^2)'
+  - x*y x/y x%y => product, division, module, left associative
+  - x+y x-y     => sum, subtraction, left associative
 ",
     )
 }
 
+fn show_vars(env: &Env) {
+    for (name, value) in env.vars() {
+        println!("{name} = {value}");
+    }
+}
+
+fn show_history(env: &Env) {
+    for (index, value) in env.history().iter().enumerate() {
+        println!("${} = {value}", index + 1);
+    }
+}
+
+fn show_funcs(env: &Env) {
+    for (name, func) in env.funcs() {
+        let params = common::fmt(&func.params, Some(", "));
+        println!("{name}({params}) = {}", func.body);
+    }
+}
+
 fn err(msg: String) {
     println!("{}", common::color(&Color::FAI, &msg));
 }
@@ -244,3 +757,56 @@ fn err(msg: String) {
 fn suc(msg: String) {
     println!("{}", common::color(&Color::SUC, &msg));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::solver::CheckRules::*;
+
+    #[test]
+    fn test_change_rules() {
+        let mut opt = Options::default();
+        opt.set(&["checks", "+deny-division", "+deny_exponent"])
+            .unwrap();
+        assert_eq!(opt.checks, [DenyDivision, DenyExponent]);
+        opt.set(&["checks", "-deny-exponent", "+deny-module"])
+            .unwrap();
+        assert_eq!(opt.checks, [DenyDivision, DenyModule]);
+        opt.set(&[
+            "checks",
+            "deny-sign",
+            "+deny-factorial",
+            "-deny-all-multiple-sign",
+        ])
+        .unwrap();
+        assert_eq!(opt.checks, [DenyMultipleSign, DenyFactorial]);
+        opt.set(&[
+            "checks",
+            "all",
+            "-deny-implicit-product",
+            "none",
+            "+deny-exponent",
+        ])
+        .unwrap();
+        assert_eq!(opt.checks, [DenyExponent]);
+        let err = opt.set(&["checks", "+deny-division", "+deny-foo"]);
+        assert_eq!(err, Err(String::from("'+deny-foo' is not a valid value!")));
+        assert!(opt.set(&["checks", "-deny-division", "some"]).is_err());
+        assert!(opt.set(&["checks", "+"]).is_err());
+        assert_eq!(opt.checks, [DenyExponent]);
+    }
+
+    #[test]
+    fn test_fmt_errors() {
+        let mut steps = vec![];
+        let res = eval(
+            "1 + x",
+            &Options::default(),
+            &mut Env::default(),
+            &mut steps,
+        );
+        let errs = res.err().unwrap_or_default();
+        let expected = "Error: SolveErr: unknown variable 'x'\n  1 + x\n      ^\n";
+        assert_eq!(fmt_errors("1 + x", &errs, "Error:", false), expected);
+    }
+}